@@ -0,0 +1,554 @@
+use crate::ast::{
+    CallExpr, CaseClause, CaseExpr, DefineExpr, DefmacroExpr, ExportExpr, Expr, GuardClause,
+    GuardExpr, IdentifierExpr, IfExpr, IntegerLiteral, LambdaExpr, Location, MacroCallExpr,
+    QuoteExpr, StringLiteral,
+};
+use crate::builtins;
+use crate::parser::ParserError;
+use crate::reader::{Datum, DatumTag};
+use crate::value::{NativeFunction, NativeThunk, Value};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+fn syntatic_error(datum: &Datum, message: &str) -> ParserError {
+    ParserError::SyntaticError {
+        location: datum.location.clone(),
+        message: String::from(message),
+    }
+}
+
+/// Turns data into expressions. It remembers the names defined by `defmacro` so that later
+/// uses of them are analyzed as macro calls.
+pub struct Analyzer {
+    pub macros: HashSet<String>,
+}
+
+impl Analyzer {
+    pub fn new() -> Analyzer {
+        Analyzer {
+            macros: HashSet::new(),
+        }
+    }
+
+    pub fn analyze(&mut self, datum: &Datum) -> Result<Rc<dyn Expr>, ParserError> {
+        let location = datum.location.clone();
+
+        match &datum.tag {
+            DatumTag::Symbol(_) => Ok(self.analyze_identifier(datum)?),
+            DatumTag::IntegerLiteral(value) => Ok(Rc::new(IntegerLiteral {
+                location,
+                value: *value,
+            })),
+            DatumTag::StringLiteral(value) => Ok(Rc::new(StringLiteral {
+                location,
+                value: value.clone(),
+            })),
+            DatumTag::BooleanLiteral(value) => Ok(Rc::new(QuoteExpr {
+                location,
+                value: Value::Boolean(*value),
+            })),
+            DatumTag::Vector(_) => Ok(Rc::new(QuoteExpr {
+                location,
+                value: datum.to_value(),
+            })),
+            DatumTag::List(items) if !items.is_empty() => self.analyze_call_like(datum, items),
+            _ => Err(syntatic_error(
+                datum,
+                "unexpected datum when parsing expression. ",
+            )),
+        }
+    }
+
+    pub fn analyze_identifier(&mut self, datum: &Datum) -> Result<Rc<IdentifierExpr>, ParserError> {
+        match datum.symbol() {
+            Some(identifier) => Ok(Rc::new(IdentifierExpr {
+                location: datum.location.clone(),
+                identifer: String::from(identifier),
+            })),
+            None => Err(syntatic_error(
+                datum,
+                "unexpected datum when parsing identifier. ",
+            )),
+        }
+    }
+
+    fn analyze_body(
+        &mut self,
+        datum: &Datum,
+        body: &[Datum],
+    ) -> Result<Vec<Rc<dyn Expr>>, ParserError> {
+        if body.is_empty() {
+            return Err(syntatic_error(
+                datum,
+                "expecting at least one expression in the body. ",
+            ));
+        }
+
+        body.iter().map(|datum| self.analyze(datum)).collect()
+    }
+
+    fn analyze_call_like(
+        &mut self,
+        datum: &Datum,
+        items: &[Datum],
+    ) -> Result<Rc<dyn Expr>, ParserError> {
+        let location = datum.location.clone();
+        let operands = &items[1..];
+
+        match items[0].symbol() {
+            Some("define") => self.analyze_define(datum, operands),
+            Some("lambda") => {
+                if operands.is_empty() {
+                    return Err(syntatic_error(
+                        datum,
+                        "expecting parameters in lambda expression. ",
+                    ));
+                }
+
+                Ok(self.analyze_lambda(datum, None, &operands[0], &operands[1..])?)
+            }
+            Some("if") => {
+                if operands.len() != 2 && operands.len() != 3 {
+                    return Err(syntatic_error(
+                        datum,
+                        "expecting (if condition consequent [alternative]). ",
+                    ));
+                }
+
+                Ok(Rc::new(IfExpr {
+                    location,
+                    condition: self.analyze(&operands[0])?,
+                    consequent: self.analyze(&operands[1])?,
+                    alternative: match operands.get(2) {
+                        Some(alternative) => Some(self.analyze(alternative)?),
+                        None => None,
+                    },
+                }))
+            }
+            Some("quote") => match datum.special_operand("quote") {
+                Some(quoted) => Ok(Rc::new(QuoteExpr {
+                    location,
+                    value: quoted.to_value(),
+                })),
+                None => Err(syntatic_error(
+                    datum,
+                    "expecting exactly one datum in quote expression. ",
+                )),
+            },
+            Some("quasiquote") => match datum.special_operand("quasiquote") {
+                Some(template) => self.analyze_quasiquote(template, 1),
+                None => Err(syntatic_error(
+                    datum,
+                    "expecting exactly one datum in quasiquote expression. ",
+                )),
+            },
+            Some("unquote" | "unquote-splicing") => {
+                Err(syntatic_error(datum, "unquote outside of quasiquote. "))
+            }
+            Some("defmacro") => {
+                if operands.len() < 2 {
+                    return Err(syntatic_error(
+                        datum,
+                        "expecting (defmacro name parameters body...). ",
+                    ));
+                }
+
+                let identifier = self.analyze_identifier(&operands[0])?;
+                let transformer = self.analyze_lambda(
+                    datum,
+                    Some(identifier.identifer.clone()),
+                    &operands[1],
+                    &operands[2..],
+                )?;
+
+                self.macros.insert(identifier.identifer.clone());
+
+                Ok(Rc::new(DefmacroExpr {
+                    location,
+                    identifier,
+                    transformer,
+                }))
+            }
+            Some("guard") => self.analyze_guard(datum, operands),
+            Some("case") => self.analyze_case(datum, operands),
+            Some("export") => {
+                let identifiers: Result<Vec<_>, _> = operands
+                    .iter()
+                    .map(|datum| self.analyze_identifier(datum))
+                    .collect();
+
+                Ok(Rc::new(ExportExpr {
+                    location,
+                    identifiers: identifiers?,
+                }))
+            }
+            Some(name) if self.macros.contains(name) => Ok(Rc::new(MacroCallExpr {
+                location,
+                identifier: self.analyze_identifier(&items[0])?,
+                operands: operands.to_vec(),
+                expansion: RefCell::new(None),
+            })),
+            _ => {
+                let function = self.analyze(&items[0])?;
+
+                let parameters: Result<Vec<_>, _> =
+                    operands.iter().map(|datum| self.analyze(datum)).collect();
+
+                Ok(Rc::new(CallExpr {
+                    location,
+                    function,
+                    parameters: parameters?,
+                }))
+            }
+        }
+    }
+
+    fn analyze_define(
+        &mut self,
+        datum: &Datum,
+        operands: &[Datum],
+    ) -> Result<Rc<dyn Expr>, ParserError> {
+        let location = datum.location.clone();
+
+        match operands.first().map(|target| &target.tag) {
+            // (define (name . parameters) body...)
+            Some(DatumTag::List(signature) | DatumTag::DottedList(signature, _))
+                if !signature.is_empty() =>
+            {
+                let identifier = self.analyze_identifier(&signature[0])?;
+
+                let parameters = match &operands[0].tag {
+                    DatumTag::DottedList(_, rest) if signature.len() == 1 => (**rest).clone(),
+                    DatumTag::DottedList(_, rest) => Datum {
+                        tag: DatumTag::DottedList(signature[1..].to_vec(), rest.clone()),
+                        location: signature[1].location.clone(),
+                    },
+                    _ => Datum {
+                        tag: DatumTag::List(signature[1..].to_vec()),
+                        location: operands[0].location.clone(),
+                    },
+                };
+
+                let value = self.analyze_lambda(
+                    datum,
+                    Some(identifier.identifer.clone()),
+                    &parameters,
+                    &operands[1..],
+                )?;
+
+                Ok(Rc::new(DefineExpr {
+                    location,
+                    identifier,
+                    value,
+                }))
+            }
+            // (define name value)
+            Some(_) if operands.len() == 2 => {
+                let identifier = self.analyze_identifier(&operands[0])?;
+
+                let value: Rc<dyn Expr> = match &operands[1].tag {
+                    DatumTag::List(items)
+                        if items.len() >= 2 && items[0].symbol() == Some("lambda") =>
+                    {
+                        self.analyze_lambda(
+                            &operands[1],
+                            Some(identifier.identifer.clone()),
+                            &items[1],
+                            &items[2..],
+                        )?
+                    }
+                    _ => self.analyze(&operands[1])?,
+                };
+
+                Ok(Rc::new(DefineExpr {
+                    location,
+                    identifier,
+                    value,
+                }))
+            }
+            _ => Err(syntatic_error(
+                datum,
+                "expecting (define name value) or (define (name parameters...) body...). ",
+            )),
+        }
+    }
+
+    fn analyze_guard(
+        &mut self,
+        datum: &Datum,
+        operands: &[Datum],
+    ) -> Result<Rc<dyn Expr>, ParserError> {
+        let specification = match operands.first().map(|datum| &datum.tag) {
+            Some(DatumTag::List(specification)) if !specification.is_empty() => specification,
+            _ => {
+                return Err(syntatic_error(
+                    datum,
+                    "expecting (guard (variable clause...) body...). ",
+                ))
+            }
+        };
+
+        let variable = self.analyze_identifier(&specification[0])?;
+
+        let mut clauses = Vec::<GuardClause>::new();
+
+        for clause in specification[1..].iter() {
+            match &clause.tag {
+                DatumTag::List(items) if !items.is_empty() => {
+                    let test = match items[0].symbol() {
+                        Some("else") => None,
+                        _ => Some(self.analyze(&items[0])?),
+                    };
+
+                    if test.is_none() && items.len() == 1 {
+                        return Err(syntatic_error(clause, "expecting a body in else clause. "));
+                    }
+
+                    let body: Result<Vec<_>, _> =
+                        items[1..].iter().map(|datum| self.analyze(datum)).collect();
+
+                    clauses.push(GuardClause { test, body: body? });
+                }
+                _ => return Err(syntatic_error(clause, "expecting (test body...). ")),
+            }
+        }
+
+        Ok(Rc::new(GuardExpr {
+            location: datum.location.clone(),
+            variable,
+            clauses,
+            body: self.analyze_body(datum, &operands[1..])?,
+        }))
+    }
+
+    fn analyze_case(
+        &mut self,
+        datum: &Datum,
+        operands: &[Datum],
+    ) -> Result<Rc<dyn Expr>, ParserError> {
+        let key = match operands.first() {
+            Some(key) => self.analyze(key)?,
+            None => {
+                return Err(syntatic_error(
+                    datum,
+                    "expecting (case key ((datum...) body...)...). ",
+                ))
+            }
+        };
+
+        let mut clauses = Vec::<CaseClause>::new();
+
+        for (i, clause) in operands[1..].iter().enumerate() {
+            let items = match &clause.tag {
+                DatumTag::List(items) if items.len() >= 2 => items,
+                _ => return Err(syntatic_error(clause, "expecting ((datum...) body...). ")),
+            };
+
+            let data = match &items[0].tag {
+                DatumTag::Symbol(symbol) if symbol == "else" => {
+                    if i + 2 != operands.len() {
+                        return Err(syntatic_error(clause, "else clause must come last. "));
+                    }
+                    None
+                }
+                DatumTag::List(data) => Some(data.iter().map(Datum::to_value).collect()),
+                _ => return Err(syntatic_error(&items[0], "expecting a list of data. ")),
+            };
+
+            clauses.push(CaseClause {
+                data,
+                body: self.analyze_body(clause, &items[1..])?,
+            });
+        }
+
+        Ok(Rc::new(CaseExpr {
+            location: datum.location.clone(),
+            key,
+            clauses,
+        }))
+    }
+
+    fn analyze_lambda(
+        &mut self,
+        datum: &Datum,
+        name: Option<String>,
+        parameters: &Datum,
+        body: &[Datum],
+    ) -> Result<Rc<LambdaExpr>, ParserError> {
+        let (fixed, rest) = match &parameters.tag {
+            DatumTag::List(fixed) => (fixed.as_slice(), None),
+            DatumTag::DottedList(fixed, rest) => (fixed.as_slice(), Some(&**rest)),
+            DatumTag::Symbol(_) => (&[] as &[Datum], Some(parameters)),
+            _ => return Err(syntatic_error(parameters, "expecting a parameter list. ")),
+        };
+
+        let fixed: Vec<_> = fixed
+            .iter()
+            .map(|datum| self.analyze_identifier(datum))
+            .collect::<Result<_, _>>()?;
+
+        let rest = match rest {
+            Some(rest) => Some(self.analyze_identifier(rest)?),
+            None => None,
+        };
+
+        let body = self.analyze_body(datum, body)?;
+
+        Ok(Rc::new_cyclic(|this| LambdaExpr {
+            location: datum.location.clone(),
+            this: this.clone(),
+            name,
+            parameters: fixed,
+            rest,
+            body,
+        }))
+    }
+
+    /// Expands a quasiquote template into calls to `list` and `append`.
+    fn analyze_quasiquote(
+        &mut self,
+        template: &Datum,
+        depth: usize,
+    ) -> Result<Rc<dyn Expr>, ParserError> {
+        match &template.tag {
+            DatumTag::List(items) => {
+                if let Some(operand) = template.special_operand("unquote") {
+                    if depth == 1 {
+                        return self.analyze(operand);
+                    }
+                    return self.analyze_quasiquote_keyword(
+                        template,
+                        "unquote",
+                        operand,
+                        depth - 1,
+                    );
+                }
+
+                if let Some(operand) = template.special_operand("unquote-splicing") {
+                    if depth == 1 {
+                        return Err(syntatic_error(
+                            template,
+                            "unquote-splicing outside of a list. ",
+                        ));
+                    }
+                    return self.analyze_quasiquote_keyword(
+                        template,
+                        "unquote-splicing",
+                        operand,
+                        depth - 1,
+                    );
+                }
+
+                if let Some(operand) = template.special_operand("quasiquote") {
+                    return self.analyze_quasiquote_keyword(
+                        template,
+                        "quasiquote",
+                        operand,
+                        depth + 1,
+                    );
+                }
+
+                self.analyze_quasiquote_list(template, items, None, depth)
+            }
+            DatumTag::DottedList(items, tail) => {
+                self.analyze_quasiquote_list(template, items, Some(tail), depth)
+            }
+            DatumTag::Vector(items) => Ok(native_call(
+                &template.location,
+                "list->vector",
+                builtins::list_to_vector,
+                vec![self.analyze_quasiquote_list(template, items, None, depth)?],
+            )),
+            _ => Ok(Rc::new(QuoteExpr {
+                location: template.location.clone(),
+                value: template.to_value(),
+            })),
+        }
+    }
+
+    fn analyze_quasiquote_keyword(
+        &mut self,
+        template: &Datum,
+        keyword: &str,
+        operand: &Datum,
+        depth: usize,
+    ) -> Result<Rc<dyn Expr>, ParserError> {
+        let location = template.location.clone();
+
+        Ok(native_call(
+            &location,
+            "list",
+            builtins::list,
+            vec![
+                Rc::new(QuoteExpr {
+                    location: location.clone(),
+                    value: Value::Symbol(String::from(keyword)),
+                }),
+                self.analyze_quasiquote(operand, depth)?,
+            ],
+        ))
+    }
+
+    fn analyze_quasiquote_list(
+        &mut self,
+        template: &Datum,
+        items: &[Datum],
+        tail: Option<&Datum>,
+        depth: usize,
+    ) -> Result<Rc<dyn Expr>, ParserError> {
+        let location = &template.location;
+        let mut segments = Vec::<Rc<dyn Expr>>::new();
+        let mut elements = Vec::<Rc<dyn Expr>>::new();
+
+        for item in items {
+            match item.special_operand("unquote-splicing") {
+                Some(spliced) if depth == 1 => {
+                    if !elements.is_empty() {
+                        segments.push(native_call(location, "list", builtins::list, elements));
+                        elements = vec![];
+                    }
+                    segments.push(self.analyze(spliced)?);
+                }
+                _ => elements.push(self.analyze_quasiquote(item, depth)?),
+            }
+        }
+
+        if !elements.is_empty() || segments.is_empty() {
+            segments.push(native_call(location, "list", builtins::list, elements));
+        }
+
+        if let Some(tail) = tail {
+            segments.push(self.analyze_quasiquote(tail, depth)?);
+        }
+
+        if segments.len() == 1 {
+            Ok(segments.pop().unwrap())
+        } else {
+            Ok(native_call(location, "append", builtins::append, segments))
+        }
+    }
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Calls a builtin directly, so that expansions don't depend on what the user has bound.
+fn native_call(
+    location: &Location,
+    name: &'static str,
+    function: NativeFunction,
+    parameters: Vec<Rc<dyn Expr>>,
+) -> Rc<dyn Expr> {
+    Rc::new(CallExpr {
+        location: location.clone(),
+        function: Rc::new(QuoteExpr {
+            location: location.clone(),
+            value: Value::NativeThunk(NativeThunk { name, function }),
+        }),
+        parameters,
+    })
+}
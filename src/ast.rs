@@ -1,15 +1,42 @@
+use std::cell::RefCell;
 use std::fmt::Debug;
-use std::rc::Rc;
+use std::ops::Range;
+use std::rc::{Rc, Weak};
 use crate::evaluate;
+use crate::printer;
+use crate::snapshot;
+use crate::reader::Datum;
+use crate::source::SourceId;
+use crate::value::Value;
 
-#[derive(Debug, Clone)]
+/// Where a node is in the source. Offsets count chars, like the lexer's.
+#[derive(Debug, Clone, Default)]
 pub struct Location {
+    pub source: SourceId,
     pub offset: i32,
     pub col: i32,
     pub row: i32,
+    /// Offset just past the last char of the node.
+    pub end_offset: i32,
 }
 
-pub trait Node: Debug + evaluate::Evaluatable {
+impl Location {
+    /// The location starting where `self` starts and ending where `end` ends.
+    pub fn to(&self, end: &Location) -> Location {
+        Location {
+            end_offset: end.end_offset,
+            ..self.clone()
+        }
+    }
+
+    /// The chars covered by the node, never empty so that it can always be underlined.
+    pub fn span(&self) -> Range<usize> {
+        let start = self.offset.max(0) as usize;
+        start..(self.end_offset.max(0) as usize).max(start + 1)
+    }
+}
+
+pub trait Node: Debug + evaluate::Evaluatable + snapshot::Encode + printer::Print {
     fn location(&self) -> &Location;
 }
 
@@ -104,3 +131,157 @@ impl Node for Program {
         &self.location
     }
 }
+
+#[derive(Debug)]
+pub struct QuoteExpr {
+    pub location: Location,
+    pub value: Value,
+}
+
+impl Expr for QuoteExpr {
+}
+
+impl Node for QuoteExpr {
+    fn location(&self) -> &Location {
+        &self.location
+    }
+}
+
+#[derive(Debug)]
+pub struct LambdaExpr {
+    pub location: Location,
+    /// Lets the closures created from this expression share it.
+    pub this: Weak<LambdaExpr>,
+    pub name: Option<String>,
+    pub parameters: Vec<Rc<IdentifierExpr>>,
+    pub rest: Option<Rc<IdentifierExpr>>,
+    pub body: Vec<Rc<dyn Expr>>,
+}
+
+impl Expr for LambdaExpr {
+}
+
+impl Node for LambdaExpr {
+    fn location(&self) -> &Location {
+        &self.location
+    }
+}
+
+#[derive(Debug)]
+pub struct IfExpr {
+    pub location: Location,
+    pub condition: Rc<dyn Expr>,
+    pub consequent: Rc<dyn Expr>,
+    pub alternative: Option<Rc<dyn Expr>>,
+}
+
+impl Expr for IfExpr {
+}
+
+impl Node for IfExpr {
+    fn location(&self) -> &Location {
+        &self.location
+    }
+}
+
+#[derive(Debug)]
+pub struct DefmacroExpr {
+    pub location: Location,
+    pub identifier: Rc<IdentifierExpr>,
+    pub transformer: Rc<LambdaExpr>,
+}
+
+impl Expr for DefmacroExpr {
+}
+
+impl Node for DefmacroExpr {
+    fn location(&self) -> &Location {
+        &self.location
+    }
+}
+
+/// A use of a macro. The operands are kept as data and handed to the transformer when the
+/// expression is first evaluated; the analyzed expansion is cached afterwards. If the name is
+/// bound to something other than a macro by then, e.g. a parameter shadowing it, the use is
+/// analyzed as an ordinary call instead.
+#[derive(Debug)]
+pub struct MacroCallExpr {
+    pub location: Location,
+    pub identifier: Rc<IdentifierExpr>,
+    pub operands: Vec<Datum>,
+    pub expansion: RefCell<Option<Rc<dyn Expr>>>,
+}
+
+impl Expr for MacroCallExpr {
+}
+
+impl Node for MacroCallExpr {
+    fn location(&self) -> &Location {
+        &self.location
+    }
+}
+
+#[derive(Debug)]
+pub struct GuardClause {
+    /// `None` for the `else` clause.
+    pub test: Option<Rc<dyn Expr>>,
+    pub body: Vec<Rc<dyn Expr>>,
+}
+
+/// `(guard (variable clause...) body...)`
+#[derive(Debug)]
+pub struct GuardExpr {
+    pub location: Location,
+    pub variable: Rc<IdentifierExpr>,
+    pub clauses: Vec<GuardClause>,
+    pub body: Vec<Rc<dyn Expr>>,
+}
+
+impl Expr for GuardExpr {
+}
+
+impl Node for GuardExpr {
+    fn location(&self) -> &Location {
+        &self.location
+    }
+}
+
+/// `(export name...)` makes names defined by a library available to `require`.
+#[derive(Debug)]
+pub struct ExportExpr {
+    pub location: Location,
+    pub identifiers: Vec<Rc<IdentifierExpr>>,
+}
+
+impl Expr for ExportExpr {
+}
+
+impl Node for ExportExpr {
+    fn location(&self) -> &Location {
+        &self.location
+    }
+}
+
+#[derive(Debug)]
+pub struct CaseClause {
+    /// `None` for the `else` clause.
+    pub data: Option<Vec<Value>>,
+    pub body: Vec<Rc<dyn Expr>>,
+}
+
+/// `(case key ((datum...) body...)...)` runs the first clause listing a datum `eqv?` to the key.
+#[derive(Debug)]
+pub struct CaseExpr {
+    pub location: Location,
+    pub key: Rc<dyn Expr>,
+    pub clauses: Vec<CaseClause>,
+}
+
+impl Expr for CaseExpr {
+}
+
+impl Node for CaseExpr {
+    fn location(&self) -> &Location {
+        &self.location
+    }
+}
@@ -0,0 +1,169 @@
+use super::{define_native, expect_arity, expect_environment, expect_string};
+use crate::isolate::{Namespace, RuntimeError};
+use crate::library;
+use crate::port::{InputPort, OutputPort};
+use crate::value::{NativeThunkInput, Value};
+use std::cell::RefCell;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+pub fn install(namespace: &mut Namespace) {
+    define_native(namespace, "open-input-file", open_input_file);
+    define_native(namespace, "open-output-file", open_output_file);
+    define_native(namespace, "file-exists?", file_exists);
+    define_native(namespace, "delete-file", delete_file);
+    define_native(namespace, "directory-list", directory_list);
+    define_native(namespace, "read-file", read_file);
+    define_native(namespace, "write-file", write_file);
+    define_native(namespace, "require", require);
+    define_native(namespace, "load", load);
+}
+
+fn io_error(path: &str, error: io::Error) -> RuntimeError {
+    RuntimeError::Io {
+        path: String::from(path),
+        message: error.to_string(),
+    }
+}
+
+pub fn open_input_file(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("open-input-file", &input, 1)?;
+    let path = expect_string("open-input-file", &input.parameters[0])?;
+    let file = File::open(path).map_err(|error| io_error(path, error))?;
+    Ok(Value::InputPort(InputPort::file(file, path)))
+}
+
+/// `(open-output-file path)` creates the file, or empties it if it exists.
+pub fn open_output_file(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("open-output-file", &input, 1)?;
+    let path = expect_string("open-output-file", &input.parameters[0])?;
+    let file = File::create(path).map_err(|error| io_error(path, error))?;
+
+    Ok(Value::OutputPort(Rc::new(RefCell::new(OutputPort::File {
+        file,
+        path: String::from(path),
+    }))))
+}
+
+pub fn file_exists(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("file-exists?", &input, 1)?;
+    let path = expect_string("file-exists?", &input.parameters[0])?;
+    Ok(Value::Boolean(Path::new(path).exists()))
+}
+
+pub fn delete_file(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("delete-file", &input, 1)?;
+    let path = expect_string("delete-file", &input.parameters[0])?;
+    fs::remove_file(path).map_err(|error| io_error(path, error))?;
+    Ok(Value::None)
+}
+
+/// `(directory-list path)` returns the names of the entries of a directory, sorted.
+pub fn directory_list(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("directory-list", &input, 1)?;
+    let path = expect_string("directory-list", &input.parameters[0])?;
+
+    let mut names = fs::read_dir(path)
+        .and_then(|entries| {
+            entries
+                .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+                .collect::<io::Result<Vec<_>>>()
+        })
+        .map_err(|error| io_error(path, error))?;
+
+    names.sort();
+
+    Ok(Value::list(names.into_iter().map(Value::String).collect()))
+}
+
+/// `(read-file path)` returns the whole content of a file as a string.
+pub fn read_file(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("read-file", &input, 1)?;
+    let path = expect_string("read-file", &input.parameters[0])?;
+    Ok(Value::String(library::read_file(Path::new(path))?))
+}
+
+/// `(write-file path string)` replaces the content of a file, creating it if needed.
+pub fn write_file(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("write-file", &input, 2)?;
+    let path = expect_string("write-file", &input.parameters[0])?;
+    let content = expect_string("write-file", &input.parameters[1])?;
+    fs::write(path, content).map_err(|error| io_error(path, error))?;
+    Ok(Value::None)
+}
+
+/// `(require "path" [name...])` loads a library and binds the given names it exports in the
+/// caller's environment, or every name it exports if none are given.
+pub fn require(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    let (name, names) = match input.parameters.split_first() {
+        Some((name, names)) => (expect_string("require", name)?, names),
+        None => {
+            return Err(RuntimeError::ArityMismatch {
+                name: String::from("require"),
+                expected: String::from("at least 1"),
+                got: 0,
+            })
+        }
+    };
+
+    let library = library::require(input.isolate, name)?;
+
+    let names = if names.is_empty() {
+        library.exports.clone()
+    } else {
+        names
+            .iter()
+            .map(|name| match name {
+                Value::Symbol(name) if library.exports.contains(name) => Ok(name.clone()),
+                Value::Symbol(name) => Err(RuntimeError::NotExported {
+                    library: library.path.display().to_string(),
+                    name: name.clone(),
+                }),
+                _ => Err(RuntimeError::TypeMismatch {
+                    name: String::from("require"),
+                    expected: String::from("symbols naming what to import"),
+                }),
+            })
+            .collect::<Result<_, _>>()?
+    };
+
+    let environment = input.isolate.current();
+
+    for name in names.iter() {
+        let value = library.environment.borrow().variables[name].clone();
+        environment.borrow_mut().bind(name, value)?;
+    }
+
+    Ok(Value::None)
+}
+
+/// `(load "path" [environment])` evaluates a file in `environment`, the global environment by
+/// default. Relative paths are resolved against the directory of the file calling `load`.
+pub fn load(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    let environment = match input.parameters.len() {
+        1 => input.isolate.global(),
+        2 => expect_environment("load", &input.parameters[1])?,
+        got => {
+            return Err(RuntimeError::ArityMismatch {
+                name: String::from("load"),
+                expected: String::from("1 or 2"),
+                got,
+            })
+        }
+    };
+
+    let path = input
+        .isolate
+        .libraries
+        .base()
+        .join(expect_string("load", &input.parameters[0])?);
+
+    let code = library::read_file(&path)?;
+    let program = library::parse(input.isolate, &path.display().to_string(), &code)?;
+    library::evaluate_file(input.isolate, &path, &program, environment)?;
+
+    Ok(Value::None)
+}
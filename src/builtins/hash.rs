@@ -0,0 +1,133 @@
+use super::{define_native, expect_arity};
+use crate::isolate::{Namespace, RuntimeError};
+use crate::value::{NativeThunkInput, Value};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+pub fn install(namespace: &mut Namespace) {
+    define_native(namespace, "make-hash-table", make_hash_table);
+    define_native(namespace, "hash-table?", is_hash_table);
+    define_native(namespace, "hash-ref", hash_ref);
+    define_native(namespace, "hash-set!", hash_set);
+    define_native(namespace, "hash-remove!", hash_remove);
+    define_native(namespace, "hash-contains?", hash_contains);
+    define_native(namespace, "hash-count", hash_count);
+    define_native(namespace, "hash-keys", hash_keys);
+    define_native(namespace, "hash-for-each", hash_for_each);
+}
+
+fn expect_hash_table(
+    name: &str,
+    value: &Value,
+) -> Result<Rc<RefCell<HashMap<Value, Value>>>, RuntimeError> {
+    match value {
+        Value::HashTable(table) => Ok(table.clone()),
+        _ => Err(RuntimeError::TypeMismatch {
+            name: String::from(name),
+            expected: String::from("a hash table"),
+        }),
+    }
+}
+
+pub fn make_hash_table(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("make-hash-table", &input, 0)?;
+    input.isolate.allocate_hash_table()
+}
+
+pub fn is_hash_table(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("hash-table?", &input, 1)?;
+    Ok(Value::Boolean(matches!(
+        input.parameters[0],
+        Value::HashTable(_)
+    )))
+}
+
+/// `(hash-ref table key [default])` returns the value of `key`, or `default` if the table has
+/// none. Without a default, a missing key is an error.
+pub fn hash_ref(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    let got = input.parameters.len();
+
+    if got != 2 && got != 3 {
+        return Err(RuntimeError::ArityMismatch {
+            name: String::from("hash-ref"),
+            expected: String::from("2 or 3"),
+            got,
+        });
+    }
+
+    let table = expect_hash_table("hash-ref", &input.parameters[0])?;
+    let key = &input.parameters[1];
+    let value = table.borrow().get(key).cloned();
+
+    value
+        .or_else(|| input.parameters.get(2).cloned())
+        .ok_or_else(|| RuntimeError::KeyNotFound {
+            name: String::from("hash-ref"),
+            key: key.clone(),
+        })
+}
+
+pub fn hash_set(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("hash-set!", &input, 3)?;
+    let table = expect_hash_table("hash-set!", &input.parameters[0])?;
+
+    let mut parameters = input.parameters.into_iter().skip(1);
+    let key = parameters.next().unwrap();
+    let value = parameters.next().unwrap();
+
+    if !table.borrow().contains_key(&key) {
+        input.isolate.grow(1)?;
+    }
+    table.borrow_mut().insert(key, value);
+
+    Ok(Value::None)
+}
+
+/// `(hash-remove! table key)` removes `key`, if the table has it.
+pub fn hash_remove(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("hash-remove!", &input, 2)?;
+    let table = expect_hash_table("hash-remove!", &input.parameters[0])?;
+    table.borrow_mut().remove(&input.parameters[1]);
+    Ok(Value::None)
+}
+
+pub fn hash_contains(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("hash-contains?", &input, 2)?;
+    let table = expect_hash_table("hash-contains?", &input.parameters[0])?;
+    let contains = table.borrow().contains_key(&input.parameters[1]);
+    Ok(Value::Boolean(contains))
+}
+
+pub fn hash_count(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("hash-count", &input, 1)?;
+    let table = expect_hash_table("hash-count", &input.parameters[0])?;
+    let count = table.borrow().len();
+    Ok(Value::Integer(count as i32))
+}
+
+/// `(hash-keys table)` returns a list of the keys, in no particular order.
+pub fn hash_keys(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("hash-keys", &input, 1)?;
+    let table = expect_hash_table("hash-keys", &input.parameters[0])?;
+    let keys = table.borrow().keys().cloned().collect();
+    Ok(Value::list(keys))
+}
+
+/// `(hash-for-each table f)` calls `(f key value)` for each entry, in no particular order.
+pub fn hash_for_each(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("hash-for-each", &input, 2)?;
+    let table = expect_hash_table("hash-for-each", &input.parameters[0])?;
+    // Copied, so that `f` may modify the table while it is iterated over.
+    let entries: Vec<(Value, Value)> = table
+        .borrow()
+        .iter()
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+
+    for (key, value) in entries {
+        input.isolate.call(&input.parameters[1], vec![key, value])?;
+    }
+
+    Ok(Value::None)
+}
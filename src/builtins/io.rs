@@ -0,0 +1,199 @@
+use super::{define_native, expect_arity, expect_string, read_datum};
+use crate::isolate::{Namespace, RuntimeError};
+use crate::port::{InputPort, OutputPort};
+use crate::value::{NativeThunkInput, Value};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub fn install(namespace: &mut Namespace) {
+    define_native(namespace, "debug", debug);
+    define_native(namespace, "read", read);
+    define_native(namespace, "display", display);
+    define_native(namespace, "write", write);
+    define_native(namespace, "newline", newline);
+    define_native(namespace, "print", print);
+    define_native(namespace, "current-input-port", current_input_port);
+    define_native(namespace, "current-output-port", current_output_port);
+    define_native(namespace, "open-input-string", open_input_string);
+    define_native(namespace, "open-output-string", open_output_string);
+    define_native(namespace, "get-output-string", get_output_string);
+    define_native(namespace, "read-line", read_line);
+    define_native(namespace, "read-char", read_char);
+}
+
+/// Checks that `name` got `required` arguments, optionally followed by a port.
+fn expect_port_arity(
+    name: &str,
+    input: &NativeThunkInput,
+    required: usize,
+) -> Result<(), RuntimeError> {
+    let got = input.parameters.len();
+
+    if got == required || got == required + 1 {
+        Ok(())
+    } else {
+        Err(RuntimeError::ArityMismatch {
+            name: String::from(name),
+            expected: format!("{} or {}", required, required + 1),
+            got,
+        })
+    }
+}
+
+/// The output port passed at `index`, or the current output port if there is none.
+fn output_port(
+    name: &str,
+    input: &NativeThunkInput,
+    index: usize,
+) -> Result<Rc<RefCell<OutputPort>>, RuntimeError> {
+    match input.parameters.get(index) {
+        None => Ok(input.isolate.output.clone()),
+        Some(Value::OutputPort(port)) => Ok(port.clone()),
+        Some(_) => Err(RuntimeError::TypeMismatch {
+            name: String::from(name),
+            expected: String::from("an output port"),
+        }),
+    }
+}
+
+/// The input port passed at `index`, or the current input port if there is none.
+fn input_port(
+    name: &str,
+    input: &NativeThunkInput,
+    index: usize,
+) -> Result<Rc<RefCell<InputPort>>, RuntimeError> {
+    match input.parameters.get(index) {
+        None => Ok(input.isolate.input.clone()),
+        Some(Value::InputPort(port)) => Ok(port.clone()),
+        Some(_) => Err(RuntimeError::TypeMismatch {
+            name: String::from(name),
+            expected: String::from("an input port"),
+        }),
+    }
+}
+
+pub fn debug(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    let text = format!("{:?}\n", input.parameters);
+    input.isolate.output.borrow_mut().write(&text)?;
+    Ok(Value::None)
+}
+
+/// `(display value [port])` prints `value` for people: strings are printed without quotes.
+pub fn display(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_port_arity("display", &input, 1)?;
+    let port = output_port("display", &input, 1)?;
+    port.borrow_mut().write(&input.parameters[0].to_string())?;
+    Ok(Value::None)
+}
+
+/// `(write value [port])` prints `value` so that `read` can read it back.
+pub fn write(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_port_arity("write", &input, 1)?;
+    let port = output_port("write", &input, 1)?;
+    port.borrow_mut()
+        .write(&input.parameters[0].written().to_string())?;
+    Ok(Value::None)
+}
+
+pub fn newline(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_port_arity("newline", &input, 0)?;
+    output_port("newline", &input, 0)?
+        .borrow_mut()
+        .write("\n")?;
+    Ok(Value::None)
+}
+
+/// `(print value...)` displays the values separated by spaces, then ends the line.
+pub fn print(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    let values: Vec<_> = input.parameters.iter().map(Value::to_string).collect();
+    let text = format!("{}\n", values.join(" "));
+    input.isolate.output.borrow_mut().write(&text)?;
+    Ok(Value::None)
+}
+
+/// `(read [port])` reads a datum, asking the port for more lines until it is complete.
+pub fn read(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_port_arity("read", &input, 0)?;
+    let port = input_port("read", &input, 0)?;
+    let mut port = port.borrow_mut();
+
+    loop {
+        let incomplete = match read_datum(&port.buffer) {
+            Ok(Some((value, consumed))) => {
+                port.consume(consumed);
+                return Ok(value);
+            }
+            Ok(None) => None,
+            Err((error, true)) => Some(error),
+            Err((error, false)) => {
+                port.buffer.clear();
+                return Err(RuntimeError::from(error));
+            }
+        };
+
+        if !port.try_fill()? {
+            port.buffer.clear();
+            return match incomplete {
+                Some(error) => Err(RuntimeError::from(error)),
+                None => Ok(Value::Eof),
+            };
+        }
+    }
+}
+
+pub fn current_input_port(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("current-input-port", &input, 0)?;
+    Ok(Value::InputPort(input.isolate.input.clone()))
+}
+
+pub fn current_output_port(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("current-output-port", &input, 0)?;
+    Ok(Value::OutputPort(input.isolate.output.clone()))
+}
+
+/// `(open-input-string string)` returns a port reading the chars of `string`.
+pub fn open_input_string(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("open-input-string", &input, 1)?;
+    let text = expect_string("open-input-string", &input.parameters[0])?;
+    Ok(Value::InputPort(InputPort::string(text)))
+}
+
+/// `(open-output-string)` returns a port collecting what is written to it, for
+/// `get-output-string`.
+pub fn open_output_string(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("open-output-string", &input, 0)?;
+    Ok(Value::OutputPort(OutputPort::string()))
+}
+
+pub fn get_output_string(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("get-output-string", &input, 1)?;
+
+    let contents = match &input.parameters[0] {
+        Value::OutputPort(port) => port.borrow().contents().map(String::from),
+        _ => None,
+    };
+
+    contents
+        .map(Value::String)
+        .ok_or_else(|| RuntimeError::TypeMismatch {
+            name: String::from("get-output-string"),
+            expected: String::from("a port made by open-output-string"),
+        })
+}
+
+/// `(read-line [port])` reads the rest of the line, without the line ending.
+pub fn read_line(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_port_arity("read-line", &input, 0)?;
+    let port = input_port("read-line", &input, 0)?;
+    let line = port.borrow_mut().read_line()?;
+    Ok(line.map_or(Value::Eof, Value::String))
+}
+
+/// `(read-char [port])` reads a char, returned as a string of length one since there is no
+/// char type.
+pub fn read_char(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_port_arity("read-char", &input, 0)?;
+    let port = input_port("read-char", &input, 0)?;
+    let c = port.borrow_mut().read_char()?;
+    Ok(c.map_or(Value::Eof, |c| Value::String(c.to_string())))
+}
@@ -0,0 +1,24 @@
+use super::{define_native, expect_arity, expect_string};
+use crate::isolate::{Namespace, RuntimeError};
+use crate::json::Json;
+use crate::value::{NativeThunkInput, Value};
+
+pub fn install(namespace: &mut Namespace) {
+    define_native(namespace, "json->value", json_to_value);
+    define_native(namespace, "value->json", value_to_json);
+}
+
+/// `(json->value text)` parses `text`. Objects become hash tables keyed by strings, arrays
+/// become vectors, and `null` becomes the symbol `null`.
+pub fn json_to_value(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("json->value", &input, 1)?;
+    let text = expect_string("json->value", &input.parameters[0])?;
+    Json::read(text, input.isolate)
+}
+
+/// `(value->json value)` returns `value` written as JSON, on one line.
+pub fn value_to_json(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("value->json", &input, 1)?;
+    let json = Json::from_value(&input.parameters[0])?;
+    Ok(Value::String(json.to_string()))
+}
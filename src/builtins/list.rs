@@ -0,0 +1,457 @@
+use super::{define_native, expect_arity, expect_integer};
+use crate::isolate::{Isolate, Namespace, RuntimeError};
+use crate::value::{NativeThunkInput, Value};
+
+pub fn install(namespace: &mut Namespace) {
+    define_native(namespace, "length", length);
+    define_native(namespace, "reverse", reverse);
+    define_native(namespace, "list-ref", list_ref);
+    define_native(namespace, "map", map);
+    define_native(namespace, "for-each", for_each);
+    define_native(namespace, "filter", filter);
+    define_native(namespace, "reduce", reduce);
+    define_native(namespace, "fold-left", fold_left);
+    define_native(namespace, "fold-right", fold_right);
+    define_native(namespace, "sort", sort);
+    define_native(namespace, "list?", is_list);
+    define_native(namespace, "last-pair", last_pair);
+    define_native(namespace, "find", find);
+    define_native(namespace, "any", any);
+    define_native(namespace, "every", every);
+    define_native(namespace, "memq", memq);
+    define_native(namespace, "memv", memv);
+    define_native(namespace, "member", member);
+    define_native(namespace, "assq", assq);
+    define_native(namespace, "assv", assv);
+    define_native(namespace, "assoc", assoc);
+}
+
+/// How `member` and `assoc` compare keys.
+enum Comparison {
+    Eqv,
+    Equal,
+    /// A procedure given by the caller.
+    Procedure(Value),
+}
+
+impl Comparison {
+    fn matches(&self, isolate: &mut Isolate, a: &Value, b: &Value) -> Result<bool, RuntimeError> {
+        match self {
+            Comparison::Eqv => Ok(a.is_eqv(b)),
+            Comparison::Equal => Ok(a == b),
+            Comparison::Procedure(procedure) => Ok(isolate
+                .call(procedure, vec![a.clone(), b.clone()])?
+                .is_truthy()),
+        }
+    }
+}
+
+/// Checks that `name` got 2 arguments, or 3 if it accepts a comparison procedure, which is
+/// returned.
+fn expect_comparison(
+    name: &str,
+    input: &NativeThunkInput,
+    default: Comparison,
+    custom: bool,
+) -> Result<Comparison, RuntimeError> {
+    match input.parameters.len() {
+        2 => Ok(default),
+        3 if custom => Ok(Comparison::Procedure(input.parameters[2].clone())),
+        got => Err(RuntimeError::ArityMismatch {
+            name: String::from(name),
+            expected: String::from(if custom { "2 or 3" } else { "2" }),
+            got,
+        }),
+    }
+}
+
+/// Returns the first sublist of `list` whose car matches `key`, or `#f`.
+fn find_member(
+    name: &str,
+    input: NativeThunkInput,
+    comparison: Comparison,
+) -> Result<Value, RuntimeError> {
+    let key = &input.parameters[0];
+    let mut cur = input.parameters[1].clone();
+
+    loop {
+        match &cur {
+            Value::Pair(pair) => {
+                if comparison.matches(input.isolate, key, &pair.car)? {
+                    return Ok(cur);
+                }
+                let next = pair.cdr.clone();
+                cur = next;
+            }
+            Value::Nil => return Ok(Value::Boolean(false)),
+            _ => {
+                return Err(RuntimeError::TypeMismatch {
+                    name: String::from(name),
+                    expected: String::from("a proper list"),
+                })
+            }
+        }
+    }
+}
+
+/// Returns the first pair of the association list `alist` whose car matches `key`, or `#f`.
+fn find_association(
+    name: &str,
+    input: NativeThunkInput,
+    comparison: Comparison,
+) -> Result<Value, RuntimeError> {
+    let key = &input.parameters[0];
+    let mut cur = input.parameters[1].clone();
+
+    loop {
+        match &cur {
+            Value::Pair(pair) => {
+                match &pair.car {
+                    Value::Pair(entry) if comparison.matches(input.isolate, key, &entry.car)? => {
+                        return Ok(pair.car.clone())
+                    }
+                    Value::Pair(_) => {}
+                    _ => {
+                        return Err(RuntimeError::TypeMismatch {
+                            name: String::from(name),
+                            expected: String::from("an association list"),
+                        })
+                    }
+                }
+                let next = pair.cdr.clone();
+                cur = next;
+            }
+            Value::Nil => return Ok(Value::Boolean(false)),
+            _ => {
+                return Err(RuntimeError::TypeMismatch {
+                    name: String::from(name),
+                    expected: String::from("an association list"),
+                })
+            }
+        }
+    }
+}
+
+pub fn memq(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    let comparison = expect_comparison("memq", &input, Comparison::Eqv, false)?;
+    find_member("memq", input, comparison)
+}
+
+pub fn memv(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    let comparison = expect_comparison("memv", &input, Comparison::Eqv, false)?;
+    find_member("memv", input, comparison)
+}
+
+/// `(member key list [compare])` compares with `equal?` unless given another procedure.
+pub fn member(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    let comparison = expect_comparison("member", &input, Comparison::Equal, true)?;
+    find_member("member", input, comparison)
+}
+
+pub fn assq(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    let comparison = expect_comparison("assq", &input, Comparison::Eqv, false)?;
+    find_association("assq", input, comparison)
+}
+
+pub fn assv(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    let comparison = expect_comparison("assv", &input, Comparison::Eqv, false)?;
+    find_association("assv", input, comparison)
+}
+
+/// `(assoc key alist [compare])` compares with `equal?` unless given another procedure.
+pub fn assoc(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    let comparison = expect_comparison("assoc", &input, Comparison::Equal, true)?;
+    find_association("assoc", input, comparison)
+}
+
+fn expect_list(name: &str, value: &Value) -> Result<Vec<Value>, RuntimeError> {
+    value.to_vec().ok_or_else(|| RuntimeError::TypeMismatch {
+        name: String::from(name),
+        expected: String::from("a proper list"),
+    })
+}
+
+/// Checks that `name` got at least `required` arguments.
+fn expect_at_least(
+    name: &str,
+    input: &NativeThunkInput,
+    required: usize,
+) -> Result<(), RuntimeError> {
+    let got = input.parameters.len();
+
+    if got >= required {
+        Ok(())
+    } else {
+        Err(RuntimeError::ArityMismatch {
+            name: String::from(name),
+            expected: format!("at least {}", required),
+            got,
+        })
+    }
+}
+
+/// The elements of the lists from `start` on, taken side by side: the first element of each
+/// list, then the second, and so on, stopping at the end of the shortest list.
+fn zip_lists(
+    name: &str,
+    input: &NativeThunkInput,
+    start: usize,
+) -> Result<Vec<Vec<Value>>, RuntimeError> {
+    let lists = input.parameters[start..]
+        .iter()
+        .map(|list| expect_list(name, list))
+        .collect::<Result<Vec<_>, _>>()?;
+    let length = lists.iter().map(Vec::len).min().unwrap_or(0);
+
+    Ok((0..length)
+        .map(|i| lists.iter().map(|list| list[i].clone()).collect())
+        .collect())
+}
+
+pub fn length(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("length", &input, 1)?;
+    let list = expect_list("length", &input.parameters[0])?;
+    Ok(Value::Integer(list.len() as i32))
+}
+
+pub fn reverse(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("reverse", &input, 1)?;
+    let mut list = expect_list("reverse", &input.parameters[0])?;
+    list.reverse();
+    Ok(Value::list(list))
+}
+
+pub fn list_ref(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("list-ref", &input, 2)?;
+    let list = expect_list("list-ref", &input.parameters[0])?;
+    let index = expect_integer("list-ref", &input.parameters[1])?;
+
+    usize::try_from(index)
+        .ok()
+        .and_then(|index| list.get(index).cloned())
+        .ok_or_else(|| RuntimeError::IndexOutOfRange {
+            name: String::from("list-ref"),
+            index,
+            length: list.len(),
+        })
+}
+
+/// `(map f list...)` returns the results of calling `f` with the elements of the lists, taken
+/// side by side.
+pub fn map(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_at_least("map", &input, 2)?;
+    let arguments = zip_lists("map", &input, 1)?;
+
+    let results = arguments
+        .into_iter()
+        .map(|arguments| input.isolate.call(&input.parameters[0], arguments))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Value::list(results))
+}
+
+/// `(for-each f list...)` is like `map`, for the side effects of `f`.
+pub fn for_each(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_at_least("for-each", &input, 2)?;
+
+    for arguments in zip_lists("for-each", &input, 1)? {
+        input.isolate.call(&input.parameters[0], arguments)?;
+    }
+
+    Ok(Value::None)
+}
+
+/// `(filter keep? list)` returns the elements for which `keep?` returns true, in order.
+pub fn filter(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("filter", &input, 2)?;
+    let list = expect_list("filter", &input.parameters[1])?;
+    let mut kept = vec![];
+
+    for element in list {
+        if input
+            .isolate
+            .call(&input.parameters[0], vec![element.clone()])?
+            .is_truthy()
+        {
+            kept.push(element);
+        }
+    }
+
+    Ok(Value::list(kept))
+}
+
+/// `(reduce f initial list)` combines the elements from left to right with `(f element
+/// accumulated)`, starting from the first element. `initial` is returned for an empty list.
+pub fn reduce(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("reduce", &input, 3)?;
+    let mut list = expect_list("reduce", &input.parameters[2])?.into_iter();
+
+    let mut accumulated = match list.next() {
+        Some(first) => first,
+        None => return Ok(input.parameters[1].clone()),
+    };
+
+    for element in list {
+        accumulated = input
+            .isolate
+            .call(&input.parameters[0], vec![element, accumulated])?;
+    }
+
+    Ok(accumulated)
+}
+
+/// `(fold-left f initial list...)` calls `(f accumulated element...)` from the first elements
+/// to the last.
+pub fn fold_left(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_at_least("fold-left", &input, 3)?;
+    let mut accumulated = input.parameters[1].clone();
+
+    for elements in zip_lists("fold-left", &input, 2)? {
+        let mut arguments = vec![accumulated];
+        arguments.extend(elements);
+        accumulated = input.isolate.call(&input.parameters[0], arguments)?;
+    }
+
+    Ok(accumulated)
+}
+
+/// `(fold-right f initial list...)` calls `(f element... accumulated)` from the last elements
+/// to the first.
+pub fn fold_right(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_at_least("fold-right", &input, 3)?;
+    let mut accumulated = input.parameters[1].clone();
+
+    for mut arguments in zip_lists("fold-right", &input, 2)?.into_iter().rev() {
+        arguments.push(accumulated);
+        accumulated = input.isolate.call(&input.parameters[0], arguments)?;
+    }
+
+    Ok(accumulated)
+}
+
+pub fn is_list(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("list?", &input, 1)?;
+    let mut cur = &input.parameters[0];
+
+    while let Value::Pair(pair) = cur {
+        cur = &pair.cdr;
+    }
+
+    Ok(Value::Boolean(matches!(cur, Value::Nil)))
+}
+
+/// `(last-pair pair)` returns the last pair of a list, whose cdr is `()` unless the list is
+/// improper.
+pub fn last_pair(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("last-pair", &input, 1)?;
+    let mut cur = input.parameters[0].clone();
+
+    loop {
+        let next = match &cur {
+            Value::Pair(pair) => match &pair.cdr {
+                next @ Value::Pair(_) => next.clone(),
+                _ => return Ok(cur),
+            },
+            _ => {
+                return Err(RuntimeError::TypeMismatch {
+                    name: String::from("last-pair"),
+                    expected: String::from("a pair"),
+                })
+            }
+        };
+        cur = next;
+    }
+}
+
+/// `(find pred list)` returns the first element for which `pred` returns true, or `#f`.
+pub fn find(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("find", &input, 2)?;
+
+    for element in expect_list("find", &input.parameters[1])? {
+        if input
+            .isolate
+            .call(&input.parameters[0], vec![element.clone()])?
+            .is_truthy()
+        {
+            return Ok(element);
+        }
+    }
+
+    Ok(Value::Boolean(false))
+}
+
+/// `(any pred list)` returns the first true result of `pred` on the elements, or `#f`.
+pub fn any(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("any", &input, 2)?;
+
+    for element in expect_list("any", &input.parameters[1])? {
+        let result = input.isolate.call(&input.parameters[0], vec![element])?;
+        if result.is_truthy() {
+            return Ok(result);
+        }
+    }
+
+    Ok(Value::Boolean(false))
+}
+
+/// `(every pred list)` returns `#f` if `pred` returns false for an element, or else its result on
+/// the last element, or `#t` for an empty list.
+pub fn every(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("every", &input, 2)?;
+    let mut result = Value::Boolean(true);
+
+    for element in expect_list("every", &input.parameters[1])? {
+        result = input.isolate.call(&input.parameters[0], vec![element])?;
+        if !result.is_truthy() {
+            return Ok(result);
+        }
+    }
+
+    Ok(result)
+}
+
+/// `(sort list less?)` returns the elements ordered by `less?`. The sort is stable: equal
+/// elements keep their order.
+pub fn sort(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("sort", &input, 2)?;
+    let list = expect_list("sort", &input.parameters[0])?;
+    let less = input.parameters[1].clone();
+    let sorted = merge_sort(input.isolate, &less, list)?;
+    Ok(Value::list(sorted))
+}
+
+/// A merge sort calling back into `less`. The standard library's sorts can't stop at the first
+/// error `less` raises, and may panic if it isn't a total order.
+fn merge_sort(
+    isolate: &mut Isolate,
+    less: &Value,
+    mut elements: Vec<Value>,
+) -> Result<Vec<Value>, RuntimeError> {
+    if elements.len() <= 1 {
+        return Ok(elements);
+    }
+
+    let right = elements.split_off(elements.len() / 2);
+    let left = merge_sort(isolate, less, elements)?;
+    let right = merge_sort(isolate, less, right)?;
+
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+
+    while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+        // Taking from the left unless the right is strictly less keeps the sort stable.
+        let right_first = isolate.call(less, vec![b.clone(), a.clone()])?.is_truthy();
+
+        merged.push(if right_first {
+            right.next().unwrap()
+        } else {
+            left.next().unwrap()
+        });
+    }
+
+    merged.extend(left);
+    merged.extend(right);
+
+    Ok(merged)
+}
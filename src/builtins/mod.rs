@@ -0,0 +1,594 @@
+use crate::analyzer::Analyzer;
+use crate::ast::Location;
+use crate::isolate::{Environment, Handler, Namespace, RuntimeError};
+use crate::parser::ParserError;
+use crate::reader::{Datum, Reader};
+use crate::value::{
+    Continuation, ErrorObject, NativeFunction, NativeThunk, NativeThunkInput, Value,
+};
+use std::cell::Cell;
+use std::rc::Rc;
+
+fn define_native(namespace: &mut Namespace, name: &'static str, function: NativeFunction) {
+    namespace
+        .bind(
+            &String::from(name),
+            Value::NativeThunk(NativeThunk { name, function }),
+        )
+        .unwrap();
+}
+
+mod fs;
+mod hash;
+mod io;
+mod json;
+mod list;
+mod process;
+mod random;
+mod time;
+mod vector;
+
+pub use vector::list_to_vector;
+
+/// A group of natives that touch the world outside the isolate. The core library, which only
+/// computes, is always installed; modules are opted into with `IsolateBuilder`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Module {
+    /// Standard input and output.
+    Io,
+    /// Files and directories.
+    Fs,
+    /// The running process: its environment variables and exit.
+    Process,
+    /// Clocks.
+    Time,
+    /// Random numbers.
+    Random,
+}
+
+impl Module {
+    pub const ALL: [Module; 5] = [
+        Module::Io,
+        Module::Fs,
+        Module::Process,
+        Module::Time,
+        Module::Random,
+    ];
+
+    pub fn install(self, namespace: &mut Namespace) {
+        match self {
+            Module::Io => io::install(namespace),
+            Module::Fs => fs::install(namespace),
+            Module::Process => process::install(namespace),
+            Module::Time => time::install(namespace),
+            Module::Random => random::install(namespace),
+        }
+    }
+}
+
+/// Installs the core library, which every isolate has.
+pub fn install(namespace: &mut Namespace) {
+    define_native(namespace, "list", list);
+    define_native(namespace, "cons", cons);
+    define_native(namespace, "car", car);
+    define_native(namespace, "cdr", cdr);
+    define_native(namespace, "append", append);
+    define_native(namespace, "null?", is_null);
+    define_native(namespace, "pair?", is_pair);
+    define_native(namespace, "read-from-string", read_from_string);
+    define_native(namespace, "eof-object", eof_object);
+    define_native(namespace, "eof-object?", is_eof_object);
+    define_native(namespace, "apply", apply);
+    define_native(namespace, "eval", eval);
+    define_native(
+        namespace,
+        "interaction-environment",
+        interaction_environment,
+    );
+    define_native(namespace, "current-environment", current_environment);
+    define_native(namespace, "make-environment", make_environment);
+    define_native(namespace, "symbol?", is_symbol);
+    define_native(namespace, "string?", is_string);
+    define_native(namespace, "eq?", is_eq);
+    define_native(namespace, "eqv?", is_eqv);
+    define_native(namespace, "equal?", is_equal);
+    define_native(namespace, "raise", raise);
+    define_native(namespace, "raise-continuable", raise_continuable);
+    define_native(namespace, "with-exception-handler", with_exception_handler);
+    define_native(namespace, "error", error);
+    define_native(namespace, "error-object?", is_error_object);
+    define_native(namespace, "error-object-kind", error_object_kind);
+    define_native(namespace, "error-object-message", error_object_message);
+    define_native(namespace, "error-object-irritants", error_object_irritants);
+    define_native(namespace, "call/cc", call_with_current_continuation);
+    define_native(
+        namespace,
+        "call-with-current-continuation",
+        call_with_current_continuation,
+    );
+    define_native(namespace, "dynamic-wind", dynamic_wind);
+    define_native(namespace, "gc", gc);
+    define_native(namespace, "heap-statistics", heap_statistics);
+    list::install(namespace);
+    vector::install(namespace);
+    hash::install(namespace);
+    json::install(namespace);
+}
+
+/// Stands in for a native that an isolate inherited from its prototype without being given its
+/// module.
+pub fn not_installed(_: NativeThunkInput) -> Result<Value, RuntimeError> {
+    Err(RuntimeError::NotInstalled)
+}
+
+fn expect_arity(name: &str, input: &NativeThunkInput, expected: usize) -> Result<(), RuntimeError> {
+    if input.parameters.len() == expected {
+        Ok(())
+    } else {
+        Err(RuntimeError::ArityMismatch {
+            name: String::from(name),
+            expected: expected.to_string(),
+            got: input.parameters.len(),
+        })
+    }
+}
+
+fn expect_environment(name: &str, value: &Value) -> Result<Environment, RuntimeError> {
+    match value {
+        Value::Environment(environment) => Ok(environment.clone()),
+        _ => Err(RuntimeError::TypeMismatch {
+            name: String::from(name),
+            expected: String::from("an environment"),
+        }),
+    }
+}
+
+fn expect_string<'a>(name: &str, value: &'a Value) -> Result<&'a str, RuntimeError> {
+    match value {
+        Value::String(string) => Ok(string),
+        _ => Err(RuntimeError::TypeMismatch {
+            name: String::from(name),
+            expected: String::from("a string"),
+        }),
+    }
+}
+
+fn expect_integer(name: &str, value: &Value) -> Result<i32, RuntimeError> {
+    match value {
+        Value::Integer(integer) => Ok(*integer),
+        _ => Err(RuntimeError::TypeMismatch {
+            name: String::from(name),
+            expected: String::from("an integer"),
+        }),
+    }
+}
+
+pub fn list(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    Ok(Value::list(input.parameters))
+}
+
+pub fn cons(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("cons", &input, 2)?;
+
+    let mut parameters = input.parameters.into_iter();
+    let car = parameters.next().unwrap();
+    let cdr = parameters.next().unwrap();
+
+    Ok(Value::cons(car, cdr))
+}
+
+pub fn car(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("car", &input, 1)?;
+
+    match &input.parameters[0] {
+        Value::Pair(pair) => Ok(pair.car.clone()),
+        _ => Err(RuntimeError::TypeMismatch {
+            name: String::from("car"),
+            expected: String::from("a pair"),
+        }),
+    }
+}
+
+pub fn cdr(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("cdr", &input, 1)?;
+
+    match &input.parameters[0] {
+        Value::Pair(pair) => Ok(pair.cdr.clone()),
+        _ => Err(RuntimeError::TypeMismatch {
+            name: String::from("cdr"),
+            expected: String::from("a pair"),
+        }),
+    }
+}
+
+/// Concatenates lists. The last parameter is shared rather than copied and may be any value.
+pub fn append(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    let mut parameters = input.parameters;
+
+    let mut result = match parameters.pop() {
+        Some(last) => last,
+        None => return Ok(Value::Nil),
+    };
+
+    for parameter in parameters.iter().rev() {
+        match parameter.to_vec() {
+            Some(values) => result = Value::list_with_tail(values, result),
+            None => {
+                return Err(RuntimeError::TypeMismatch {
+                    name: String::from("append"),
+                    expected: String::from("proper lists"),
+                })
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+pub fn is_null(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("null?", &input, 1)?;
+    Ok(Value::Boolean(matches!(input.parameters[0], Value::Nil)))
+}
+
+pub fn is_pair(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("pair?", &input, 1)?;
+    Ok(Value::Boolean(matches!(
+        input.parameters[0],
+        Value::Pair(_)
+    )))
+}
+
+/// Reads the first datum of `code`, returning it with the number of chars it took.
+fn read_datum(code: &str) -> Result<Option<(Value, usize)>, (ParserError, bool)> {
+    let mut reader = Reader::new(code);
+
+    reader
+        .init()
+        .map_err(|error| (ParserError::from(error), reader.is_exhausted()))?;
+
+    if reader.is_at_end() {
+        return Ok(None);
+    }
+
+    match reader.read() {
+        Ok(datum) => {
+            let consumed = datum.location.end_offset as usize;
+            Ok(Some((datum.to_value(), consumed)))
+        }
+        Err(error) => Err((error, reader.is_exhausted())),
+    }
+}
+
+pub fn read_from_string(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("read-from-string", &input, 1)?;
+
+    match &input.parameters[0] {
+        Value::String(code) => match read_datum(code) {
+            Ok(Some((value, _))) => Ok(value),
+            Ok(None) => Ok(Value::Eof),
+            Err((error, _)) => Err(RuntimeError::from(error)),
+        },
+        _ => Err(RuntimeError::TypeMismatch {
+            name: String::from("read-from-string"),
+            expected: String::from("a string"),
+        }),
+    }
+}
+
+pub fn eof_object(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("eof-object", &input, 0)?;
+    Ok(Value::Eof)
+}
+
+pub fn is_eof_object(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("eof-object?", &input, 1)?;
+    Ok(Value::Boolean(matches!(input.parameters[0], Value::Eof)))
+}
+
+/// `(apply f a b '(c d))` calls `f` with `a`, `b`, `c` and `d`.
+pub fn apply(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    let mut parameters = input.parameters;
+
+    if parameters.len() < 2 {
+        return Err(RuntimeError::ArityMismatch {
+            name: String::from("apply"),
+            expected: String::from("at least 2"),
+            got: parameters.len(),
+        });
+    }
+
+    let spread = match parameters.pop().unwrap().to_vec() {
+        Some(spread) => spread,
+        None => {
+            return Err(RuntimeError::TypeMismatch {
+                name: String::from("apply"),
+                expected: String::from("a proper list as its last argument"),
+            })
+        }
+    };
+
+    let function = parameters.remove(0);
+    parameters.extend(spread);
+
+    input.isolate.call(&function, parameters)
+}
+
+/// `(eval datum [environment])` runs `datum` as code, in the global environment by default.
+pub fn eval(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    let environment = match input.parameters.len() {
+        1 => input.isolate.global(),
+        2 => expect_environment("eval", &input.parameters[1])?,
+        got => {
+            return Err(RuntimeError::ArityMismatch {
+                name: String::from("eval"),
+                expected: String::from("1 or 2"),
+                got,
+            })
+        }
+    };
+
+    let datum = Datum::from_value(&input.parameters[0], &Location::default())
+        .map_err(|message| RuntimeError::SyntaxError { message })?;
+
+    let mut analyzer = Analyzer {
+        macros: input.isolate.macros.clone(),
+    };
+
+    let expr = analyzer.analyze(&datum)?;
+
+    input.isolate.evaluate_in(expr.as_ref(), environment)
+}
+
+pub fn interaction_environment(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("interaction-environment", &input, 0)?;
+    Ok(Value::Environment(input.isolate.global()))
+}
+
+/// The environment of the caller.
+pub fn current_environment(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("current-environment", &input, 0)?;
+    Ok(Value::Environment(input.isolate.current()))
+}
+
+/// `(make-environment [parent])` creates an empty environment inheriting from `parent`, which
+/// defaults to the global environment.
+pub fn make_environment(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    let parent = match input.parameters.len() {
+        0 => input.isolate.global(),
+        1 => expect_environment("make-environment", &input.parameters[0])?,
+        got => {
+            return Err(RuntimeError::ArityMismatch {
+                name: String::from("make-environment"),
+                expected: String::from("0 or 1"),
+                got,
+            })
+        }
+    };
+
+    Ok(Value::Environment(
+        input.isolate.allocate(Namespace::with_parent(parent))?,
+    ))
+}
+
+pub fn is_symbol(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("symbol?", &input, 1)?;
+    Ok(Value::Boolean(matches!(
+        input.parameters[0],
+        Value::Symbol(_)
+    )))
+}
+
+pub fn is_string(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("string?", &input, 1)?;
+    Ok(Value::Boolean(matches!(
+        input.parameters[0],
+        Value::String(_)
+    )))
+}
+
+/// `(eq? a b)` compares objects by identity. Since strings have none and integers aren't boxed,
+/// it is the same as `eqv?`.
+pub fn is_eq(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("eq?", &input, 2)?;
+    Ok(Value::Boolean(
+        input.parameters[0].is_eqv(&input.parameters[1]),
+    ))
+}
+
+pub fn is_eqv(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("eqv?", &input, 2)?;
+    Ok(Value::Boolean(
+        input.parameters[0].is_eqv(&input.parameters[1]),
+    ))
+}
+
+/// `(equal? a b)` compares pairs and vectors by their elements.
+pub fn is_equal(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("equal?", &input, 2)?;
+    Ok(Value::Boolean(input.parameters[0] == input.parameters[1]))
+}
+
+pub fn raise(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("raise", &input, 1)?;
+
+    Err(RuntimeError::Raised {
+        value: input.parameters.into_iter().next().unwrap(),
+    })
+}
+
+/// Calls the innermost handler right away and returns what it returns. The handler itself runs
+/// with the outer handlers installed. If the innermost handler is a guard, the condition is
+/// raised, which unwinds the stack to it.
+pub fn raise_continuable(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("raise-continuable", &input, 1)?;
+
+    let value = input.parameters.into_iter().next().unwrap();
+
+    let handler = match input.isolate.handlers.last() {
+        Some(Handler::Procedure(handler)) => handler.clone(),
+        Some(Handler::Guard) | None => return Err(RuntimeError::Raised { value }),
+    };
+
+    let innermost = input.isolate.handlers.pop().unwrap();
+    let result = input.isolate.call(&handler, vec![value]);
+    input.isolate.handlers.push(innermost);
+
+    result
+}
+
+/// `(with-exception-handler handler thunk)` calls `thunk`. If it raises, `handler` is called
+/// with the condition once the stack has unwound and its result is returned instead.
+///
+/// This isn't R7RS, where `raise` calls the handler without unwinding and the handler may not
+/// return: here, it catches like a `guard` whose clauses are a procedure. Only
+/// `raise-continuable` calls it without unwinding, and returns what it returns.
+pub fn with_exception_handler(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("with-exception-handler", &input, 2)?;
+
+    let handler = input.parameters[0].clone();
+
+    input
+        .isolate
+        .handlers
+        .push(Handler::Procedure(handler.clone()));
+    let result = input.isolate.call(&input.parameters[1], vec![]);
+    input.isolate.handlers.pop();
+
+    match result {
+        Err(error) if error.is_catchable() => {
+            input.isolate.call(&handler, vec![error.to_condition()])
+        }
+        result => result,
+    }
+}
+
+/// `(error message irritant...)` raises a new error object.
+pub fn error(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    let mut parameters = input.parameters.into_iter();
+
+    let message = match parameters.next() {
+        Some(Value::String(message)) => message,
+        _ => {
+            return Err(RuntimeError::TypeMismatch {
+                name: String::from("error"),
+                expected: String::from("a message string"),
+            })
+        }
+    };
+
+    Err(RuntimeError::Raised {
+        value: Value::Error(Rc::new(ErrorObject {
+            kind: String::from("error"),
+            message,
+            irritants: parameters.collect(),
+        })),
+    })
+}
+
+fn expect_error_object(
+    name: &str,
+    input: &NativeThunkInput,
+) -> Result<Rc<ErrorObject>, RuntimeError> {
+    expect_arity(name, input, 1)?;
+
+    match &input.parameters[0] {
+        Value::Error(error) => Ok(error.clone()),
+        _ => Err(RuntimeError::TypeMismatch {
+            name: String::from(name),
+            expected: String::from("an error object"),
+        }),
+    }
+}
+
+pub fn is_error_object(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("error-object?", &input, 1)?;
+    Ok(Value::Boolean(matches!(
+        input.parameters[0],
+        Value::Error(_)
+    )))
+}
+
+pub fn error_object_kind(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    let error = expect_error_object("error-object-kind", &input)?;
+    Ok(Value::Symbol(error.kind.clone()))
+}
+
+pub fn error_object_message(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    let error = expect_error_object("error-object-message", &input)?;
+    Ok(Value::String(error.message.clone()))
+}
+
+pub fn error_object_irritants(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    let error = expect_error_object("error-object-irritants", &input)?;
+    Ok(Value::list(error.irritants.clone()))
+}
+
+/// Calls its parameter with an escape continuation. Invoking the continuation while this call is
+/// in progress makes the call return the value passed to it.
+pub fn call_with_current_continuation(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("call/cc", &input, 1)?;
+
+    let continuation = Rc::new(Continuation {
+        active: Cell::new(true),
+    });
+
+    let result = input.isolate.call(
+        &input.parameters[0],
+        vec![Value::Continuation(continuation.clone())],
+    );
+
+    continuation.active.set(false);
+
+    match result {
+        Err(RuntimeError::ContinuationInvoked {
+            continuation: invoked,
+            value,
+        }) if Rc::ptr_eq(&invoked, &continuation) => Ok(value),
+        result => result,
+    }
+}
+
+/// `(dynamic-wind before thunk after)` calls the three in order. `after` is called even when
+/// control leaves `thunk` through a continuation or an exception.
+pub fn dynamic_wind(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("dynamic-wind", &input, 3)?;
+
+    input.isolate.call(&input.parameters[0], vec![])?;
+
+    let result = input.isolate.call(&input.parameters[1], vec![]);
+
+    input.isolate.call(&input.parameters[2], vec![])?;
+
+    result
+}
+
+/// `(gc)` frees the objects kept alive only by cycles, and returns how many were freed.
+pub fn gc(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("gc", &input, 0)?;
+    Ok(Value::Integer(input.isolate.heap.collect() as i32))
+}
+
+/// `(heap-statistics)` returns an association list describing the heap, e.g.
+/// `((environments . 12) (vectors . 2) (hash-tables . 0) (collections . 1) (freed . 3))`.
+pub fn heap_statistics(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("heap-statistics", &input, 0)?;
+    let statistics = input.isolate.heap.statistics();
+
+    Ok(Value::list(
+        [
+            ("environments", statistics.environments),
+            ("vectors", statistics.vectors),
+            ("hash-tables", statistics.hash_tables),
+            ("collections", statistics.collections),
+            ("freed", statistics.freed),
+        ]
+        .into_iter()
+        .map(|(name, count)| {
+            Value::cons(
+                Value::Symbol(String::from(name)),
+                Value::Integer(count as i32),
+            )
+        })
+        .collect(),
+    ))
+}
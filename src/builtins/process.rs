@@ -0,0 +1,56 @@
+use super::{define_native, expect_arity, expect_integer, expect_string};
+use crate::isolate::{Namespace, RuntimeError};
+use crate::value::{NativeThunkInput, Value};
+use std::env;
+use std::process;
+
+pub fn install(namespace: &mut Namespace) {
+    define_native(namespace, "command-line", command_line);
+    define_native(namespace, "exit", exit);
+    define_native(
+        namespace,
+        "get-environment-variable",
+        get_environment_variable,
+    );
+}
+
+/// The name of the program followed by its arguments, as a list of strings.
+pub fn command_line(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("command-line", &input, 0)?;
+    Ok(Value::list(
+        input
+            .isolate
+            .command_line
+            .iter()
+            .map(|argument| Value::String(argument.clone()))
+            .collect(),
+    ))
+}
+
+/// `(exit [code])` ends the process, successfully unless `code` says otherwise.
+pub fn exit(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    let code = match input.parameters.as_slice() {
+        [] => 0,
+        [code] => expect_integer("exit", code)?,
+        _ => {
+            return Err(RuntimeError::ArityMismatch {
+                name: String::from("exit"),
+                expected: String::from("0 or 1"),
+                got: input.parameters.len(),
+            })
+        }
+    };
+
+    process::exit(code)
+}
+
+/// The value of an environment variable as a string, or `#f` if it isn't set.
+pub fn get_environment_variable(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("get-environment-variable", &input, 1)?;
+    let name = expect_string("get-environment-variable", &input.parameters[0])?;
+
+    Ok(match env::var(name) {
+        Ok(value) => Value::String(value),
+        Err(_) => Value::Boolean(false),
+    })
+}
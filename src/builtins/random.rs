@@ -0,0 +1,39 @@
+use super::{define_native, expect_arity, expect_integer};
+use crate::isolate::{Namespace, RuntimeError};
+use crate::value::{NativeThunkInput, Value};
+
+pub fn install(namespace: &mut Namespace) {
+    define_native(namespace, "random", random);
+    define_native(namespace, "random-seed!", random_seed);
+}
+
+/// `(random n)` returns an integer in `[0, n)`.
+pub fn random(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("random", &input, 1)?;
+    let bound = expect_integer("random", &input.parameters[0])?;
+
+    if bound <= 0 {
+        return Err(RuntimeError::TypeMismatch {
+            name: String::from("random"),
+            expected: String::from("a positive integer"),
+        });
+    }
+
+    // xorshift64*
+    let state = &mut input.isolate.random_state;
+    *state ^= *state >> 12;
+    *state ^= *state << 25;
+    *state ^= *state >> 27;
+    let bits = state.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 32;
+
+    Ok(Value::Integer((bits % bound as u64) as i32))
+}
+
+/// `(random-seed! n)` makes the following random numbers reproducible.
+pub fn random_seed(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("random-seed!", &input, 1)?;
+    let seed = expect_integer("random-seed!", &input.parameters[0])?;
+    // xorshift gets stuck at zero, so the seed is mixed with a nonzero constant.
+    input.isolate.random_state = seed as u64 ^ 0x9e37_79b9_7f4a_7c15;
+    Ok(Value::None)
+}
@@ -0,0 +1,36 @@
+use super::{define_native, expect_arity};
+use crate::isolate::{Namespace, RuntimeError};
+use crate::value::{NativeThunkInput, Value};
+use std::sync::OnceLock;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+const JIFFIES_PER_SECOND: i32 = 1000;
+
+pub fn install(namespace: &mut Namespace) {
+    define_native(namespace, "current-time", current_time);
+    define_native(namespace, "current-jiffy", current_jiffy);
+    define_native(namespace, "jiffies-per-second", jiffies_per_second);
+}
+
+/// Seconds since the Unix epoch.
+pub fn current_time(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("current-time", &input, 0)?;
+    let elapsed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok(Value::Integer(elapsed.as_secs() as i32))
+}
+
+/// Milliseconds since the first time a jiffy was asked for, for measuring intervals.
+pub fn current_jiffy(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    static START: OnceLock<Instant> = OnceLock::new();
+
+    expect_arity("current-jiffy", &input, 0)?;
+    let elapsed = START.get_or_init(Instant::now).elapsed();
+    Ok(Value::Integer(elapsed.as_millis() as i32))
+}
+
+pub fn jiffies_per_second(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("jiffies-per-second", &input, 0)?;
+    Ok(Value::Integer(JIFFIES_PER_SECOND))
+}
@@ -0,0 +1,151 @@
+use super::{define_native, expect_arity, expect_integer};
+use crate::isolate::{Namespace, RuntimeError};
+use crate::value::{NativeThunkInput, Value};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub fn install(namespace: &mut Namespace) {
+    define_native(namespace, "vector", vector);
+    define_native(namespace, "make-vector", make_vector);
+    define_native(namespace, "vector?", is_vector);
+    define_native(namespace, "vector-length", vector_length);
+    define_native(namespace, "vector-ref", vector_ref);
+    define_native(namespace, "vector-set!", vector_set);
+    define_native(namespace, "vector-fill!", vector_fill);
+    define_native(namespace, "vector->list", vector_to_list);
+    define_native(namespace, "list->vector", list_to_vector);
+    define_native(namespace, "vector-map", vector_map);
+}
+
+fn expect_vector(name: &str, value: &Value) -> Result<Rc<RefCell<Vec<Value>>>, RuntimeError> {
+    match value {
+        Value::Vector(vector) => Ok(vector.clone()),
+        _ => Err(RuntimeError::TypeMismatch {
+            name: String::from(name),
+            expected: String::from("a vector"),
+        }),
+    }
+}
+
+/// Checks that `value` is a valid index into a vector of `length` elements.
+fn expect_index(name: &str, value: &Value, length: usize) -> Result<usize, RuntimeError> {
+    let index = expect_integer(name, value)?;
+
+    if index >= 0 && (index as usize) < length {
+        Ok(index as usize)
+    } else {
+        Err(RuntimeError::IndexOutOfRange {
+            name: String::from(name),
+            index,
+            length,
+        })
+    }
+}
+
+pub fn vector(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    input.isolate.allocate_vector(input.parameters)
+}
+
+/// `(make-vector n [fill])` returns a vector of `n` elements, all `fill`, or unspecified if it
+/// is left out.
+pub fn make_vector(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    let got = input.parameters.len();
+
+    if got != 1 && got != 2 {
+        return Err(RuntimeError::ArityMismatch {
+            name: String::from("make-vector"),
+            expected: String::from("1 or 2"),
+            got,
+        });
+    }
+
+    let length = expect_integer("make-vector", &input.parameters[0])?;
+
+    if length < 0 {
+        return Err(RuntimeError::TypeMismatch {
+            name: String::from("make-vector"),
+            expected: String::from("a non-negative length"),
+        });
+    }
+
+    // Checked before the elements are allocated, which could fail and abort the process.
+    input.isolate.reserve(1 + length as usize)?;
+
+    let fill = input.parameters.get(1).cloned().unwrap_or(Value::None);
+    input.isolate.allocate_vector(vec![fill; length as usize])
+}
+
+pub fn is_vector(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("vector?", &input, 1)?;
+    Ok(Value::Boolean(matches!(
+        input.parameters[0],
+        Value::Vector(_)
+    )))
+}
+
+pub fn vector_length(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("vector-length", &input, 1)?;
+    let vector = expect_vector("vector-length", &input.parameters[0])?;
+    let length = vector.borrow().len();
+    Ok(Value::Integer(length as i32))
+}
+
+pub fn vector_ref(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("vector-ref", &input, 2)?;
+    let vector = expect_vector("vector-ref", &input.parameters[0])?;
+    let vector = vector.borrow();
+    let index = expect_index("vector-ref", &input.parameters[1], vector.len())?;
+    Ok(vector[index].clone())
+}
+
+pub fn vector_set(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("vector-set!", &input, 3)?;
+    let vector = expect_vector("vector-set!", &input.parameters[0])?;
+    let mut vector = vector.borrow_mut();
+    let index = expect_index("vector-set!", &input.parameters[1], vector.len())?;
+    vector[index] = input.parameters[2].clone();
+    Ok(Value::None)
+}
+
+pub fn vector_fill(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("vector-fill!", &input, 2)?;
+    let vector = expect_vector("vector-fill!", &input.parameters[0])?;
+    vector.borrow_mut().fill(input.parameters[1].clone());
+    Ok(Value::None)
+}
+
+pub fn vector_to_list(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("vector->list", &input, 1)?;
+    let vector = expect_vector("vector->list", &input.parameters[0])?;
+    let elements = vector.borrow().clone();
+    Ok(Value::list(elements))
+}
+
+pub fn list_to_vector(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("list->vector", &input, 1)?;
+
+    let elements = input.parameters[0]
+        .to_vec()
+        .ok_or_else(|| RuntimeError::TypeMismatch {
+            name: String::from("list->vector"),
+            expected: String::from("a proper list"),
+        })?;
+
+    input.isolate.allocate_vector(elements)
+}
+
+/// `(vector-map f vector)` returns a new vector of `f` applied to each element.
+pub fn vector_map(input: NativeThunkInput) -> Result<Value, RuntimeError> {
+    expect_arity("vector-map", &input, 2)?;
+    let vector = expect_vector("vector-map", &input.parameters[1])?;
+    // Copied, so that `f` may modify the vector while it is mapped over.
+    let elements = vector.borrow().clone();
+
+    let mut results = Vec::with_capacity(elements.len());
+
+    for element in elements {
+        results.push(input.isolate.call(&input.parameters[0], vec![element])?);
+    }
+
+    input.isolate.allocate_vector(results)
+}
@@ -1,6 +1,10 @@
 use crate::ast::*;
-use crate::isolate::{Isolate, RuntimeError};
-use crate::value::{NativeThunkInput, Value};
+use crate::isolate::{Frame, Handler, Isolate, Namespace, RuntimeError};
+use crate::analyzer::Analyzer;
+use crate::parser::ParserError;
+use crate::reader::Datum;
+use crate::value::{Thunk, Value};
+use std::rc::Rc;
 
 pub trait Evaluatable {
     fn evaluate(&self, isolate: &mut Isolate) -> Result<Value, RuntimeError>;
@@ -9,7 +13,9 @@ pub trait Evaluatable {
 impl Evaluatable for DefineExpr {
     fn evaluate(&self, isolate: &mut Isolate) -> Result<Value, RuntimeError> {
         let value = self.value.evaluate(isolate)?;
-        isolate.bind(&self.identifier.identifer, value)?;
+        isolate
+            .bind(&self.identifier.identifer, value)
+            .map_err(|error| isolate.trace(error, "define", &self.location))?;
         Ok(Value::None)
     }
 }
@@ -19,7 +25,7 @@ impl Evaluatable for CallExpr {
         let function_value = self.function.evaluate(isolate)?;
 
         match function_value {
-            Value::NativeThunk(native_thunk) => {
+            Value::NativeThunk(_) | Value::Thunk(_) | Value::Continuation(_) => {
                 let parameters_result: Result<Vec<_>, _> = self
                     .parameters
                     .iter()
@@ -28,11 +34,24 @@ impl Evaluatable for CallExpr {
 
                 let parameters = parameters_result?;
 
-                let input = NativeThunkInput { parameters };
+                isolate.stack.push(Frame {
+                    name: function_value.procedure_name(),
+                    location: self.location.clone(),
+                });
 
-                (native_thunk.function)(input)
+                let result = isolate
+                    .call(&function_value, parameters)
+                    .map_err(|error| error.traced(&isolate.stack));
+
+                isolate.stack.pop();
+
+                result
             }
-            _ => Err(RuntimeError::NotCallable { name: "function".to_string() }), // TODO: restore the AST to string
+            _ => Err(isolate.trace(
+                RuntimeError::NotCallable { name: function_value.procedure_name() },
+                "call",
+                &self.location,
+            )),
         }
     }
 }
@@ -40,9 +59,13 @@ impl Evaluatable for CallExpr {
 impl Evaluatable for IdentifierExpr {
     fn evaluate(&self, isolate: &mut Isolate) -> Result<Value, RuntimeError> {
         if let Some(v) = isolate.resolve(&self.identifer) {
-            Ok(v.clone())
+            Ok(v)
         } else {
-            Err(RuntimeError::Unbound { name: self.identifer.clone() })
+            Err(isolate.trace(
+                RuntimeError::Unbound { name: self.identifer.clone() },
+                &self.identifer,
+                &self.location,
+            ))
         }
     }
 }
@@ -72,3 +95,251 @@ impl Evaluatable for Program {
         Ok(Value::None)
     }
 }
+
+impl Evaluatable for QuoteExpr {
+    fn evaluate(&self, isolate: &mut Isolate) -> Result<Value, RuntimeError> {
+        // Vectors in the code are copied, so that changing one doesn't change the program, and
+        // so that the heap tracks them like other vectors.
+        copy_vectors(&self.value, isolate)
+    }
+}
+
+/// `value`, with every vector in it replaced by a new one allocated by `isolate`.
+fn copy_vectors(value: &Value, isolate: &mut Isolate) -> Result<Value, RuntimeError> {
+    match value {
+        Value::Vector(items) => {
+            let items = items
+                .borrow()
+                .iter()
+                .map(|item| copy_vectors(item, isolate))
+                .collect::<Result<_, _>>()?;
+            isolate.allocate_vector(items)
+        }
+        Value::Pair(_) if contains_vector(value) => {
+            let mut items = vec![];
+            let mut cur = value;
+
+            while let Value::Pair(pair) = cur {
+                items.push(copy_vectors(&pair.car, isolate)?);
+                cur = &pair.cdr;
+            }
+
+            let tail = copy_vectors(cur, isolate)?;
+            Ok(Value::list_with_tail(items, tail))
+        }
+        _ => Ok(value.clone()),
+    }
+}
+
+fn contains_vector(value: &Value) -> bool {
+    let mut cur = value;
+
+    loop {
+        match cur {
+            Value::Vector(_) => return true,
+            Value::Pair(pair) => {
+                if contains_vector(&pair.car) {
+                    return true;
+                }
+                cur = &pair.cdr;
+            }
+            _ => return false,
+        }
+    }
+}
+
+impl Evaluatable for LambdaExpr {
+    fn evaluate(&self, isolate: &mut Isolate) -> Result<Value, RuntimeError> {
+        Ok(Value::Thunk(Thunk {
+            source: self.this.upgrade().unwrap(),
+            closure: isolate.namespaces.last().unwrap().clone(),
+        }))
+    }
+}
+
+impl Evaluatable for IfExpr {
+    fn evaluate(&self, isolate: &mut Isolate) -> Result<Value, RuntimeError> {
+        if self.condition.evaluate(isolate)?.is_truthy() {
+            self.consequent.evaluate(isolate)
+        } else if let Some(alternative) = &self.alternative {
+            alternative.evaluate(isolate)
+        } else {
+            Ok(Value::None)
+        }
+    }
+}
+
+impl Evaluatable for DefmacroExpr {
+    fn evaluate(&self, isolate: &mut Isolate) -> Result<Value, RuntimeError> {
+        let transformer = Thunk {
+            source: self.transformer.clone(),
+            closure: isolate.namespaces.last().unwrap().clone(),
+        };
+
+        isolate
+            .bind(&self.identifier.identifer, Value::Macro(transformer))
+            .map_err(|error| isolate.trace(error, "defmacro", &self.location))?;
+        isolate.macros.insert(self.identifier.identifer.clone());
+
+        Ok(Value::None)
+    }
+}
+
+impl Evaluatable for MacroCallExpr {
+    fn evaluate(&self, isolate: &mut Isolate) -> Result<Value, RuntimeError> {
+        let cached = self.expansion.borrow().clone();
+
+        let expansion = match cached {
+            Some(expansion) => expansion,
+            None => {
+                let expansion = self.expand(isolate).map_err(|error| {
+                    isolate.trace(error, &self.identifier.identifer, &self.location)
+                })?;
+                *self.expansion.borrow_mut() = Some(expansion.clone());
+                expansion
+            }
+        };
+
+        expansion.evaluate(isolate)
+    }
+}
+
+impl MacroCallExpr {
+    fn expand(&self, isolate: &mut Isolate) -> Result<Rc<dyn Expr>, RuntimeError> {
+        let name = &self.identifier.identifer;
+
+        let transformer = match isolate.resolve(name) {
+            Some(Value::Macro(transformer)) => transformer,
+            // The macro is shadowed, e.g. by a definition or a parameter of the same name.
+            Some(_) => return self.as_call(isolate),
+            None => return Err(RuntimeError::Unbound { name: name.clone() }),
+        };
+
+        let operands = self.operands.iter().map(Datum::to_value).collect();
+
+        let expanded = isolate.call(&Value::Thunk(transformer), operands)?;
+
+        let datum = Datum::from_value(&expanded, &self.location)
+            .map_err(|message| RuntimeError::SyntaxError { message })?;
+
+        let mut analyzer = Analyzer {
+            macros: isolate.macros.clone(),
+        };
+
+        analyzer.analyze(&datum).map_err(RuntimeError::from)
+    }
+
+    /// The use as an ordinary call, with the operands analyzed as its parameters.
+    fn as_call(&self, isolate: &mut Isolate) -> Result<Rc<dyn Expr>, RuntimeError> {
+        let mut analyzer = Analyzer {
+            macros: isolate.macros.clone(),
+        };
+
+        let parameters: Result<Vec<_>, _> =
+            self.operands.iter().map(|datum| analyzer.analyze(datum)).collect();
+
+        Ok(Rc::new(CallExpr {
+            location: self.location.clone(),
+            function: self.identifier.clone(),
+            parameters: parameters.map_err(RuntimeError::from)?,
+        }))
+    }
+}
+
+fn evaluate_sequence(exprs: &[Rc<dyn Expr>], isolate: &mut Isolate) -> Result<Value, RuntimeError> {
+    let mut result = Value::None;
+
+    for expr in exprs.iter() {
+        result = expr.evaluate(isolate)?;
+    }
+
+    Ok(result)
+}
+
+impl Evaluatable for GuardExpr {
+    fn evaluate(&self, isolate: &mut Isolate) -> Result<Value, RuntimeError> {
+        // So that `raise-continuable` unwinds to the guard rather than calling outer handlers.
+        isolate.handlers.push(Handler::Guard);
+        let result = evaluate_sequence(&self.body, isolate);
+        isolate.handlers.pop();
+
+        let error = match result {
+            Ok(value) => return Ok(value),
+            Err(error) if error.is_catchable() => error,
+            Err(error) => return Err(error),
+        };
+
+        let mut namespace = Namespace::with_parent(isolate.current());
+        namespace.bind(&self.variable.identifer, error.to_condition())?;
+
+        let environment = isolate.allocate(namespace)?;
+        isolate.namespaces.push(environment);
+        let result = self.handle(isolate);
+        isolate.namespaces.pop();
+
+        // Nothing matched, so the error goes on to outer handlers.
+        result.unwrap_or(Err(error))
+    }
+}
+
+impl GuardExpr {
+    /// Runs the first clause whose test passes, or returns `None` if there is none.
+    fn handle(&self, isolate: &mut Isolate) -> Option<Result<Value, RuntimeError>> {
+        for clause in self.clauses.iter() {
+            let passed = match &clause.test {
+                Some(test) => match test.evaluate(isolate) {
+                    Ok(value) if clause.body.is_empty() && value.is_truthy() => {
+                        return Some(Ok(value))
+                    }
+                    Ok(value) => value.is_truthy(),
+                    Err(error) => return Some(Err(error)),
+                },
+                None => true,
+            };
+
+            if passed {
+                return Some(evaluate_sequence(&clause.body, isolate));
+            }
+        }
+
+        None
+    }
+}
+
+impl Evaluatable for CaseExpr {
+    fn evaluate(&self, isolate: &mut Isolate) -> Result<Value, RuntimeError> {
+        let key = self.key.evaluate(isolate)?;
+
+        for clause in self.clauses.iter() {
+            let matched = match &clause.data {
+                Some(data) => data.iter().any(|datum| datum.is_eqv(&key)),
+                None => true,
+            };
+
+            if matched {
+                return evaluate_sequence(&clause.body, isolate);
+            }
+        }
+
+        Ok(Value::None)
+    }
+}
+
+impl Evaluatable for ExportExpr {
+    fn evaluate(&self, isolate: &mut Isolate) -> Result<Value, RuntimeError> {
+        isolate.libraries.export(
+            self.identifiers
+                .iter()
+                .map(|identifier| identifier.identifer.clone()),
+        );
+        Ok(Value::None)
+    }
+}
+
+impl From<ParserError> for RuntimeError {
+    fn from(error: ParserError) -> Self {
+        RuntimeError::SyntaxError {
+            message: error.to_string(),
+        }
+    }
+}
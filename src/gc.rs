@@ -0,0 +1,304 @@
+use crate::isolate::{Environment, Namespace};
+use crate::value::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+/// Collection runs once this many objects are tracked, and at least twice as many as survived
+/// the previous collection.
+const INITIAL_THRESHOLD: usize = 1024;
+
+/// A mutable container created by an isolate, which may end up in a reference cycle.
+#[derive(Clone)]
+pub enum Object {
+    Environment(Environment),
+    Vector(Rc<RefCell<Vec<Value>>>),
+    HashTable(Rc<RefCell<HashMap<Value, Value>>>),
+}
+
+enum Tracked {
+    Environment(Weak<RefCell<Namespace>>),
+    Vector(Weak<RefCell<Vec<Value>>>),
+    HashTable(Weak<RefCell<HashMap<Value, Value>>>),
+}
+
+impl Tracked {
+    fn upgrade(&self) -> Option<Object> {
+        match self {
+            Tracked::Environment(environment) => environment.upgrade().map(Object::Environment),
+            Tracked::Vector(vector) => vector.upgrade().map(Object::Vector),
+            Tracked::HashTable(table) => table.upgrade().map(Object::HashTable),
+        }
+    }
+
+    fn is_alive(&self) -> bool {
+        match self {
+            Tracked::Environment(environment) => environment.strong_count() > 0,
+            Tracked::Vector(vector) => vector.strong_count() > 0,
+            Tracked::HashTable(table) => table.strong_count() > 0,
+        }
+    }
+}
+
+impl Object {
+    fn address(&self) -> *const () {
+        match self {
+            Object::Environment(environment) => Rc::as_ptr(environment) as *const (),
+            Object::Vector(vector) => Rc::as_ptr(vector) as *const (),
+            Object::HashTable(table) => Rc::as_ptr(table) as *const (),
+        }
+    }
+
+    /// The heap slots the object takes: one, plus one per element of a vector or hash table.
+    fn slots(&self) -> usize {
+        match self {
+            Object::Environment(_) => 1,
+            Object::Vector(vector) => 1 + vector.borrow().len(),
+            Object::HashTable(table) => 1 + table.borrow().len(),
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Object::Environment(environment) => Rc::strong_count(environment),
+            Object::Vector(vector) => Rc::strong_count(vector),
+            Object::HashTable(table) => Rc::strong_count(table),
+        }
+    }
+
+    /// Calls `visit` with the address of every object this one refers to.
+    fn trace(&self, owned_only: bool, visit: &mut dyn FnMut(*const ())) {
+        match self {
+            Object::Environment(environment) => {
+                let namespace = environment.borrow();
+
+                if let Some(parent) = &namespace.parent {
+                    visit(Rc::as_ptr(parent) as *const ());
+                }
+
+                for value in namespace.variables.values() {
+                    trace_value(value, owned_only, visit);
+                }
+            }
+            Object::Vector(vector) => {
+                for value in vector.borrow().iter() {
+                    trace_value(value, owned_only, visit);
+                }
+            }
+            Object::HashTable(table) => {
+                for (key, value) in table.borrow().iter() {
+                    trace_value(key, owned_only, visit);
+                    trace_value(value, owned_only, visit);
+                }
+            }
+        }
+    }
+
+    /// Drops every reference the object holds, breaking the cycles it is part of.
+    fn clear(&self) {
+        match self {
+            Object::Environment(environment) => {
+                let mut namespace = environment.borrow_mut();
+                namespace.variables.clear();
+                namespace.parent = None;
+            }
+            Object::Vector(vector) => vector.borrow_mut().clear(),
+            Object::HashTable(table) => table.borrow_mut().clear(),
+        }
+    }
+}
+
+/// Keeps track of every environment, vector and hash table created by an isolate, so that the
+/// ones kept alive only by reference cycles can be freed.
+///
+/// Values are reference counted, which frees everything except cycles: a recursive closure is
+/// bound in the environment it captures, so neither is ever dropped. Like CPython's collector,
+/// `collect` subtracts the references objects hold to each other from their reference counts.
+/// What is left comes from outside the heap (the isolate, the Rust stack, values nobody traces),
+/// so those objects are roots. Objects not reachable from a root are cleared, which breaks their
+/// cycles and lets `Rc` free them.
+pub struct Heap {
+    objects: Vec<Tracked>,
+    /// Slots taken by the objects tracked, as of their creation or the last collection.
+    slots: usize,
+    threshold: usize,
+    collections: usize,
+    freed: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct HeapStatistics {
+    /// Environments that are still alive.
+    pub environments: usize,
+    /// Vectors that are still alive, including those made by evaluating vector literals.
+    pub vectors: usize,
+    pub hash_tables: usize,
+    pub collections: usize,
+    /// Objects freed by collections so far.
+    pub freed: usize,
+}
+
+impl Heap {
+    pub fn new() -> Heap {
+        Heap {
+            objects: vec![],
+            slots: 0,
+            threshold: INITIAL_THRESHOLD,
+            collections: 0,
+            freed: 0,
+        }
+    }
+
+    pub fn track(&mut self, object: &Object) {
+        self.slots += object.slots();
+        self.objects.push(match object {
+            Object::Environment(environment) => Tracked::Environment(Rc::downgrade(environment)),
+            Object::Vector(vector) => Tracked::Vector(Rc::downgrade(vector)),
+            Object::HashTable(table) => Tracked::HashTable(Rc::downgrade(table)),
+        });
+    }
+
+    /// The number of objects tracked, including those freed since the last collection.
+    pub fn tracked(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// The heap slots taken by the objects tracked, including those freed since the last
+    /// collection, and elements added to them since.
+    pub fn slots(&self) -> usize {
+        self.slots
+    }
+
+    /// Counts `slots` more slots, taken by elements added to an object after its creation.
+    pub fn grow(&mut self, slots: usize) {
+        self.slots += slots;
+    }
+
+    /// Whether enough objects have been created since the last collection to run another.
+    pub fn should_collect(&self) -> bool {
+        self.objects.len() >= self.threshold
+    }
+
+    pub fn statistics(&self) -> HeapStatistics {
+        let mut statistics = HeapStatistics {
+            environments: 0,
+            vectors: 0,
+            hash_tables: 0,
+            collections: self.collections,
+            freed: self.freed,
+        };
+
+        for object in self.objects.iter().filter(|object| object.is_alive()) {
+            match object {
+                Tracked::Environment(_) => statistics.environments += 1,
+                Tracked::Vector(_) => statistics.vectors += 1,
+                Tracked::HashTable(_) => statistics.hash_tables += 1,
+            }
+        }
+
+        statistics
+    }
+
+    /// Frees the objects that are only kept alive by cycles, and returns how many there were.
+    pub fn collect(&mut self) -> usize {
+        self.objects.retain(Tracked::is_alive);
+
+        let objects: Vec<Object> = self.objects.iter().filter_map(Tracked::upgrade).collect();
+        let indices: HashMap<*const (), usize> = objects
+            .iter()
+            .enumerate()
+            .map(|(i, object)| (object.address(), i))
+            .collect();
+
+        // References from outside the heap. `objects` itself holds one of each.
+        let mut external: Vec<usize> = objects
+            .iter()
+            .map(|object| object.strong_count() - 1)
+            .collect();
+
+        for object in objects.iter() {
+            object.trace(true, &mut |referent| {
+                if let Some(&i) = indices.get(&referent) {
+                    external[i] -= 1;
+                }
+            });
+        }
+
+        let mut reachable = vec![false; objects.len()];
+        let mut pending: Vec<usize> = (0..objects.len()).filter(|&i| external[i] > 0).collect();
+
+        while let Some(i) = pending.pop() {
+            if reachable[i] {
+                continue;
+            }
+            reachable[i] = true;
+
+            objects[i].trace(false, &mut |referent| {
+                if let Some(&j) = indices.get(&referent) {
+                    if !reachable[j] {
+                        pending.push(j);
+                    }
+                }
+            });
+        }
+
+        let mut freed = 0;
+
+        for (i, object) in objects.iter().enumerate() {
+            if !reachable[i] {
+                object.clear();
+                freed += 1;
+            }
+        }
+
+        drop(objects);
+
+        self.objects.retain(Tracked::is_alive);
+        self.slots = self
+            .objects
+            .iter()
+            .filter_map(Tracked::upgrade)
+            .map(|object| object.slots())
+            .sum();
+        self.threshold = INITIAL_THRESHOLD.max(self.objects.len() * 2);
+        self.collections += 1;
+        self.freed += freed;
+
+        freed
+    }
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Calls `visit` with the address of every object `value` refers to. With `owned_only`, values
+/// that are shared (e.g. a pair bound in two places) are not looked into: references they hold
+/// can't be attributed to a single owner, so the objects they refer to are conservatively kept.
+fn trace_value(value: &Value, owned_only: bool, visit: &mut dyn FnMut(*const ())) {
+    let mut cur = value;
+
+    loop {
+        match cur {
+            Value::Thunk(thunk) | Value::Macro(thunk) => {
+                return visit(Rc::as_ptr(&thunk.closure) as *const ())
+            }
+            Value::Environment(environment) => return visit(Rc::as_ptr(environment) as *const ()),
+            Value::Vector(vector) => return visit(Rc::as_ptr(vector) as *const ()),
+            Value::HashTable(table) => return visit(Rc::as_ptr(table) as *const ()),
+            Value::Error(error) if !owned_only || Rc::strong_count(error) == 1 => {
+                for irritant in error.irritants.iter() {
+                    trace_value(irritant, owned_only, visit);
+                }
+                return;
+            }
+            Value::Pair(pair) if !owned_only || Rc::strong_count(pair) == 1 => {
+                trace_value(&pair.car, owned_only, visit);
+                cur = &pair.cdr;
+            }
+            _ => return,
+        }
+    }
+}
@@ -1,12 +1,148 @@
-use crate::value::{NativeThunk, Thunk, Value};
-use std::collections::HashMap;
+use crate::ast::{Expr, Location};
+use crate::builtins;
+use crate::builtins::Module;
+use crate::gc::{Heap, Object};
+use crate::library::Libraries;
+use crate::parser::ParserError;
+use crate::port::{InputPort, OutputPort};
+use crate::prelude;
+use crate::source::{SourceId, Sources};
+use crate::value::{
+    Continuation, ErrorObject, NativeThunk, NativeThunkInput, Pair, Thunk, Value,
+};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(fmt::Debug)]
 pub enum RuntimeError {
     AlreadyBound { name: String },
     Unbound { name: String },
     NotCallable { name: String },
+    /// A native was inherited from a prototype, but the isolate wasn't given its module.
+    NotInstalled,
+    ArityMismatch { name: String, expected: String, got: usize },
+    TypeMismatch { name: String, expected: String },
+    SyntaxError { message: String },
+    /// A value thrown by `raise` or `error`.
+    Raised { value: Value },
+    /// A continuation was invoked after its `call/cc` returned. Re-entry isn't supported.
+    ExpiredContinuation,
+    /// Not an error: a continuation was invoked, and the stack unwinds up to its `call/cc`.
+    ContinuationInvoked { continuation: Rc<Continuation>, value: Value },
+    /// The isolate ran out of fuel: it made more calls than `Limits::fuel` allows.
+    FuelExhausted { limit: u64 },
+    /// More calls were nested than `Limits::max_call_depth` allows.
+    CallDepthExceeded { limit: usize },
+    /// More heap slots were in use than `Limits::max_heap` allows, even after collecting.
+    HeapExhausted { limit: usize },
+    /// `hash-ref` looked up a key the table doesn't have, and was given no default.
+    KeyNotFound { name: String, key: Value },
+    /// An index past the end of a vector.
+    IndexOutOfRange { name: String, index: i32, length: usize },
+    /// `require` couldn't find a library.
+    LibraryNotFound { name: String },
+    /// Libraries required each other. `cycle` lists their paths, starting and ending with the
+    /// same one.
+    ImportCycle { cycle: Vec<String> },
+    /// `require` asked for a name the library doesn't export.
+    NotExported { library: String, name: String },
+    /// Code loaded at runtime, e.g. by `load`, didn't parse.
+    Unparsable { source: SourceId, errors: Vec<ParserError> },
+    /// Reading or writing a file failed.
+    Io { path: String, message: String },
+    /// A value, e.g. a port, has no representation in `format`.
+    Unserializable { value: String, format: String },
+    /// A snapshot couldn't be restored.
+    InvalidSnapshot { message: String },
+    /// `json->value` got text that isn't JSON, or a number that isn't an integer. `offset`
+    /// counts characters.
+    InvalidJson { message: String, offset: usize },
+    /// An error along with the stack of frames that were active when it was raised.
+    Traced { error: Box<RuntimeError>, frames: Vec<Frame> },
+}
+
+impl RuntimeError {
+    /// Whether handlers may catch this. Control flow unwinding the stack can't be caught.
+    pub fn is_catchable(&self) -> bool {
+        !matches!(self, Self::ContinuationInvoked { .. })
+    }
+
+    /// Attaches `frames`, outermost first, unless the error already carries a stack.
+    pub fn traced(self, frames: &[Frame]) -> RuntimeError {
+        match self {
+            Self::Traced { .. } | Self::ContinuationInvoked { .. } => self,
+            error => Self::Traced {
+                error: Box::new(error),
+                frames: frames.to_vec(),
+            },
+        }
+    }
+
+    /// The error that was raised, whether or not it carries a stack.
+    pub fn cause(&self) -> &RuntimeError {
+        match self {
+            Self::Traced { error, .. } => error,
+            error => error,
+        }
+    }
+
+    /// The error without its stack.
+    pub fn untraced(self) -> RuntimeError {
+        match self {
+            Self::Traced { error, .. } => *error,
+            error => error,
+        }
+    }
+
+    /// The value a handler receives for this error: the raised value itself, or an error object
+    /// describing an internal error.
+    pub fn to_condition(&self) -> Value {
+        let (kind, irritants) = match self {
+            Self::Traced { error, .. } => return error.to_condition(),
+            Self::Raised { value } => return value.clone(),
+            Self::AlreadyBound { name } => ("already-bound", vec![name]),
+            Self::Unbound { name } => ("unbound", vec![name]),
+            Self::NotCallable { name } => ("not-callable", vec![name]),
+            Self::NotInstalled => ("not-installed", vec![]),
+            Self::ArityMismatch { name, .. } => ("arity-mismatch", vec![name]),
+            Self::TypeMismatch { name, .. } => ("type-mismatch", vec![name]),
+            Self::SyntaxError { .. } => ("syntax-error", vec![]),
+            Self::ExpiredContinuation => ("expired-continuation", vec![]),
+            Self::ContinuationInvoked { .. } => ("continuation", vec![]),
+            Self::FuelExhausted { .. } => ("fuel-exhausted", vec![]),
+            Self::CallDepthExceeded { .. } => ("call-depth-exceeded", vec![]),
+            Self::HeapExhausted { .. } => ("heap-exhausted", vec![]),
+            Self::IndexOutOfRange { name, .. } => ("index-out-of-range", vec![name]),
+            Self::KeyNotFound { name, .. } => ("key-not-found", vec![name]),
+            Self::LibraryNotFound { name } => ("library-not-found", vec![name]),
+            Self::ImportCycle { .. } => ("import-cycle", vec![]),
+            Self::NotExported { name, .. } => ("not-exported", vec![name]),
+            Self::Unparsable { .. } => ("syntax-error", vec![]),
+            Self::Io { .. } => ("io-error", vec![]),
+            Self::Unserializable { .. } => ("unserializable", vec![]),
+            Self::InvalidSnapshot { .. } => ("invalid-snapshot", vec![]),
+            Self::InvalidJson { .. } => ("invalid-json", vec![]),
+        };
+
+        Value::Error(Rc::new(ErrorObject {
+            kind: String::from(kind),
+            message: self.to_string(),
+            irritants: irritants
+                .into_iter()
+                .map(|name| Value::Symbol(name.clone()))
+                .collect(),
+        }))
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        match self {
+            Self::Traced { frames, .. } => frames,
+            _ => &[],
+        }
+    }
 }
 
 impl fmt::Display for RuntimeError {
@@ -21,18 +157,110 @@ impl fmt::Display for RuntimeError {
             Self::NotCallable { name } => {
                 write!(f, "{:?} is not callable. ", name)
             }
+            Self::NotInstalled => {
+                write!(f, "the module of this native isn't installed in the isolate. ")
+            }
+            Self::ArityMismatch { name, expected, got } => {
+                write!(f, "{:?} expects {} argument(s), got {}. ", name, expected, got)
+            }
+            Self::TypeMismatch { name, expected } => {
+                write!(f, "{:?} expects {}. ", name, expected)
+            }
+            Self::SyntaxError { message } => {
+                write!(f, "syntax error: {}", message)
+            }
+            Self::Raised { value: Value::Error(error) } => {
+                write!(f, "{}", error.message)?;
+                for irritant in error.irritants.iter() {
+                    write!(f, " {}", irritant.written())?;
+                }
+                Ok(())
+            }
+            Self::Raised { value } => {
+                write!(f, "uncaught exception: {}", value.written())
+            }
+            Self::ExpiredContinuation => {
+                write!(f, "continuations can't be re-entered once their call/cc has returned. ")
+            }
+            Self::ContinuationInvoked { .. } => {
+                write!(f, "continuation invoked outside of its call/cc. ")
+            }
+            Self::FuelExhausted { limit } => {
+                write!(f, "out of fuel after {} calls. ", limit)
+            }
+            Self::CallDepthExceeded { limit } => {
+                write!(f, "calls nested deeper than {} levels. ", limit)
+            }
+            Self::HeapExhausted { limit } => {
+                write!(f, "more than {} heap slots in use. ", limit)
+            }
+            Self::KeyNotFound { name, key } => {
+                write!(f, "{}: no value for key {}. ", name, key.written())
+            }
+            Self::IndexOutOfRange { name, index, length } => {
+                write!(f, "{}: index {} out of range for length {}. ", name, index, length)
+            }
+            Self::LibraryNotFound { name } => {
+                write!(f, "library {:?} not found. ", name)
+            }
+            Self::ImportCycle { cycle } => {
+                write!(f, "libraries require each other: {}. ", cycle.join(" -> "))
+            }
+            Self::NotExported { library, name } => {
+                write!(f, "{:?} is not exported by {}. ", name, library)
+            }
+            Self::Unparsable { errors, .. } => {
+                for error in errors.iter() {
+                    write!(f, "syntax error: {}", error)?;
+                }
+                Ok(())
+            }
+            Self::Io { path, message } => {
+                write!(f, "{}: {}. ", path, message)
+            }
+            Self::Unserializable { value, format } => {
+                write!(f, "{} can't be serialized as {}. ", value, format)
+            }
+            Self::InvalidSnapshot { message } => {
+                write!(f, "invalid snapshot: {}. ", message)
+            }
+            Self::InvalidJson { message, offset } => {
+                write!(f, "invalid JSON at character {}: {}. ", offset, message)
+            }
+            Self::Traced { error, .. } => {
+                write!(f, "{}", error)
+            }
         }
     }
 }
 
+/// A call in progress, or the expression at which an error was raised.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub name: String,
+    pub location: Location,
+}
+
 pub struct Namespace {
     pub variables: HashMap<String, Value>,
+    pub parent: Option<Environment>,
 }
 
+/// A namespace shared between the isolate and the closures capturing it.
+pub type Environment = Rc<RefCell<Namespace>>;
+
 impl Namespace {
     pub fn new() -> Namespace {
         Namespace {
             variables: HashMap::new(),
+            parent: None,
+        }
+    }
+
+    pub fn with_parent(parent: Environment) -> Namespace {
+        Namespace {
+            variables: HashMap::new(),
+            parent: Some(parent),
         }
     }
 
@@ -46,44 +274,663 @@ impl Namespace {
     }
 }
 
+impl fmt::Debug for Namespace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Values bound here may refer back to this namespace, so only the names are printed.
+        f.debug_set().entries(self.variables.keys()).finish()
+    }
+}
+
+impl Default for Namespace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Something that handles conditions raised while it is installed.
+#[derive(Debug, Clone)]
+pub enum Handler {
+    /// A procedure installed by `with-exception-handler`.
+    Procedure(Value),
+    /// A `guard` whose body is being evaluated. Conditions reach it by unwinding the stack.
+    Guard,
+}
+
+/// Bounds on the resources an isolate may use, so that untrusted code can't run forever or
+/// exhaust memory. `None` means unlimited.
+#[derive(Debug, Clone, Default)]
+pub struct Limits {
+    /// How many calls the isolate may make until `Isolate::refuel` is called.
+    pub fuel: Option<u64>,
+    /// How deeply calls may nest.
+    pub max_call_depth: Option<usize>,
+    /// How many heap slots may be in use at once. Each environment, vector and hash table takes
+    /// one, and vectors and hash tables one more per element.
+    pub max_heap: Option<usize>,
+}
+
 pub struct Isolate {
-    stack: Vec<Thunk>,
-    pub namespaces: Vec<Namespace>,
+    /// Calls in progress, the innermost one last.
+    pub stack: Vec<Frame>,
+    /// Environments of the active calls, the innermost one last.
+    pub namespaces: Vec<Environment>,
+    /// The parent of the global environment, holding the natives, or the definitions copied
+    /// from a prototype. Libraries are evaluated in children of it, so they don't see the
+    /// definitions of the program requiring them.
+    pub root: Environment,
+    pub libraries: Libraries,
+    /// The code of every program, library and loaded file, for error reports.
+    pub sources: Sources,
+    /// Names that have ever been bound by `defmacro`, consulted when analyzing code at runtime.
+    pub macros: HashSet<String>,
+    /// Handlers installed by `with-exception-handler` and guards, the innermost one last.
+    pub handlers: Vec<Handler>,
+    /// Where `read`, `read-line` and `read-char` read from by default.
+    pub input: Rc<RefCell<InputPort>>,
+    /// Where `display`, `write` and friends write to by default. Embedders may set it to a
+    /// string port to capture the output.
+    pub output: Rc<RefCell<OutputPort>>,
+    /// Every environment, vector and hash table created so far, for the cycle collector.
+    pub heap: Heap,
+    pub limits: Limits,
+    /// Calls made since the isolate was built or last refuelled with `refuel`.
+    pub steps: u64,
+    /// Calls in progress, including those of natives, which have no frame on `stack`.
+    pub depth: usize,
+    /// State of the generator behind `random`.
+    pub random_state: u64,
+    /// What `command-line` returns: the name of the program, then its arguments.
+    pub command_line: Vec<String>,
 }
 
 impl Isolate {
+    /// An isolate with every native module installed, for trusted scripts.
     pub fn new() -> Isolate {
-        let mut global_namespace = Namespace::new();
-
-        global_namespace
-            .bind(
-                &String::from("debug"),
-                Value::NativeThunk(NativeThunk {
-                    function: |input| {
-                        println!("{:?}", input.parameters);
-                        Ok(Value::None)
-                    },
-                }),
-            )
-            .unwrap();
-
-        Isolate {
-            stack: vec![],
-            namespaces: vec![global_namespace],
+        IsolateBuilder::new().all_modules().build()
+    }
+
+    /// Gives the isolate a full tank of fuel, e.g. before running another program.
+    ///
+    /// Fuel isn't refilled otherwise, not even by a new program or `load`, so that code can't
+    /// give itself more. Once it runs out, every call fails, including calls made by a `guard`
+    /// that caught the error: the isolate can only make calls again after `refuel`.
+    ///
+    /// ```
+    /// use rlisp::evaluate::Evaluatable;
+    /// use rlisp::isolate::{IsolateBuilder, Limits, RuntimeError};
+    /// use rlisp::library;
+    ///
+    /// let mut isolate = IsolateBuilder::new()
+    ///     .limits(Limits {
+    ///         fuel: Some(100),
+    ///         ..Limits::default()
+    ///     })
+    ///     .build();
+    ///
+    /// let code = "(define (forever) (forever))
+    ///             (guard (e (#t (list 'caught))) (forever))";
+    /// let program = library::parse(&mut isolate, "spin", code).unwrap();
+    /// let error = program.evaluate(&mut isolate).unwrap_err();
+    /// assert!(matches!(error.cause(), RuntimeError::FuelExhausted { .. }));
+    ///
+    /// isolate.refuel();
+    /// let program = library::parse(&mut isolate, "after", "(list 'usable)").unwrap();
+    /// assert!(program.evaluate(&mut isolate).is_ok());
+    /// ```
+    pub fn refuel(&mut self) {
+        self.steps = 0;
+    }
+
+    /// Makes room for `slots` more heap slots, collecting first if it is due. Natives check
+    /// this before allocating elements, so that a huge length fails rather than aborting.
+    ///
+    /// ```
+    /// use rlisp::evaluate::Evaluatable;
+    /// use rlisp::isolate::{IsolateBuilder, Limits, RuntimeError};
+    /// use rlisp::library;
+    ///
+    /// let mut isolate = IsolateBuilder::new()
+    ///     .limits(Limits {
+    ///         max_heap: Some(100),
+    ///         ..Limits::default()
+    ///     })
+    ///     .build();
+    ///
+    /// for code in [
+    ///     "(make-vector 2000000000 0)",
+    ///     "(define table (make-hash-table))
+    ///      (define (fill keys)
+    ///        (if (pair? keys) (fill (car (list (cdr keys) (hash-set! table keys 0))))))
+    ///      (fill (vector->list (make-vector 60 'key)))",
+    /// ] {
+    ///     let program = library::parse(&mut isolate, "grow", code).unwrap();
+    ///     let error = program.evaluate(&mut isolate).unwrap_err();
+    ///     assert!(matches!(error.cause(), RuntimeError::HeapExhausted { limit: 100 }));
+    /// }
+    /// ```
+    pub fn reserve(&mut self, slots: usize) -> Result<(), RuntimeError> {
+        let fits = |heap: &Heap, limit: Option<usize>| match limit {
+            Some(limit) => heap.slots().saturating_add(slots) <= limit,
+            None => true,
+        };
+
+        if !fits(&self.heap, self.limits.max_heap) || self.heap.should_collect() {
+            self.heap.collect();
+        }
+
+        match self.limits.max_heap {
+            Some(limit) if !fits(&self.heap, Some(limit)) => {
+                Err(RuntimeError::HeapExhausted { limit })
+            }
+            _ => Ok(()),
         }
     }
 
+    /// Takes `slots` more heap slots for elements added to an existing object.
+    pub fn grow(&mut self, slots: usize) -> Result<(), RuntimeError> {
+        self.reserve(slots)?;
+        self.heap.grow(slots);
+        Ok(())
+    }
+
+    /// Creates an environment tracked by the cycle collector.
+    pub fn allocate(&mut self, namespace: Namespace) -> Result<Environment, RuntimeError> {
+        self.reserve(1)?;
+        let environment = Rc::new(RefCell::new(namespace));
+        self.heap.track(&Object::Environment(environment.clone()));
+        Ok(environment)
+    }
+
+    /// Creates a vector tracked by the cycle collector.
+    pub fn allocate_vector(&mut self, elements: Vec<Value>) -> Result<Value, RuntimeError> {
+        self.reserve(1 + elements.len())?;
+        let vector = Rc::new(RefCell::new(elements));
+        self.heap.track(&Object::Vector(vector.clone()));
+        Ok(Value::Vector(vector))
+    }
+
+    /// Creates an empty hash table tracked by the cycle collector.
+    pub fn allocate_hash_table(&mut self) -> Result<Value, RuntimeError> {
+        self.reserve(1)?;
+        let table = Rc::new(RefCell::new(HashMap::new()));
+        self.heap.track(&Object::HashTable(table.clone()));
+        Ok(Value::HashTable(table))
+    }
+
+    pub fn global(&self) -> Environment {
+        self.namespaces.first().unwrap().clone()
+    }
+
+    pub fn current(&self) -> Environment {
+        self.namespaces.last().unwrap().clone()
+    }
+
+    /// Evaluates `expr` with `environment` as the innermost namespace.
+    pub fn evaluate_in(
+        &mut self,
+        expr: &dyn Expr,
+        environment: Environment,
+    ) -> Result<Value, RuntimeError> {
+        self.namespaces.push(environment);
+        let result = expr.evaluate(self);
+        self.namespaces.pop();
+        result
+    }
+
+    /// Attaches the current stack to `error`, topped by a frame for the expression raising it.
+    pub fn trace(&self, error: RuntimeError, name: &str, location: &Location) -> RuntimeError {
+        let mut frames = self.stack.clone();
+        frames.push(Frame {
+            name: String::from(name),
+            location: location.clone(),
+        });
+        error.traced(&frames)
+    }
+
     pub fn bind(&mut self, name: &String, value: Value) -> Result<(), RuntimeError> {
-        self.namespaces.last_mut().unwrap().bind(name, value)
+        self.namespaces.last().unwrap().borrow_mut().bind(name, value)
     }
 
-    pub fn resolve(&self, name: &String) -> Option<&Value> {
-        for ns in self.namespaces.iter().rev() {
-            if ns.variables.contains_key(name) {
-                return ns.variables.get(name);
+    pub fn resolve(&self, name: &String) -> Option<Value> {
+        let mut namespace = Some(self.namespaces.last().unwrap().clone());
+
+        while let Some(ns) = namespace {
+            if let Some(value) = ns.borrow().variables.get(name) {
+                return Some(value.clone());
             }
+            namespace = ns.borrow().parent.clone();
         }
 
         None
     }
+
+    /// Calls `function`, counting the call against the isolate's limits.
+    pub fn call(
+        &mut self,
+        function: &Value,
+        parameters: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        if let Some(limit) = self.limits.fuel {
+            if self.steps >= limit {
+                return Err(RuntimeError::FuelExhausted { limit });
+            }
+        }
+
+        if let Some(limit) = self.limits.max_call_depth {
+            if self.depth >= limit {
+                return Err(RuntimeError::CallDepthExceeded { limit });
+            }
+        }
+
+        self.steps += 1;
+        self.depth += 1;
+        let result = self.dispatch(function, parameters);
+        self.depth -= 1;
+
+        result
+    }
+
+    fn dispatch(
+        &mut self,
+        function: &Value,
+        parameters: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        match function {
+            Value::NativeThunk(native_thunk) => {
+                (native_thunk.function)(NativeThunkInput {
+                    parameters,
+                    isolate: self,
+                })
+            }
+            Value::Thunk(thunk) => self.call_thunk(thunk, parameters),
+            Value::Continuation(continuation) => {
+                if !continuation.active.get() {
+                    return Err(RuntimeError::ExpiredContinuation);
+                }
+
+                let mut parameters = parameters.into_iter();
+
+                Err(RuntimeError::ContinuationInvoked {
+                    continuation: continuation.clone(),
+                    value: parameters.next().unwrap_or(Value::None),
+                })
+            }
+            _ => Err(RuntimeError::NotCallable { name: "function".to_string() }),
+        }
+    }
+
+    fn call_thunk(&mut self, thunk: &Thunk, parameters: Vec<Value>) -> Result<Value, RuntimeError> {
+        let source = &thunk.source;
+        let mut namespace = Namespace::with_parent(thunk.closure.clone());
+
+        let arity_mismatch = match source.rest {
+            None => parameters.len() != source.parameters.len(),
+            Some(_) => parameters.len() < source.parameters.len(),
+        };
+
+        if arity_mismatch {
+            return Err(RuntimeError::ArityMismatch {
+                name: source.name.clone().unwrap_or_else(|| "lambda".to_string()),
+                expected: match source.rest {
+                    None => source.parameters.len().to_string(),
+                    Some(_) => format!("at least {}", source.parameters.len()),
+                },
+                got: parameters.len(),
+            });
+        }
+
+        let mut parameters = parameters.into_iter();
+
+        for identifier in source.parameters.iter() {
+            namespace.bind(&identifier.identifer, parameters.next().unwrap())?;
+        }
+
+        if let Some(rest) = &source.rest {
+            namespace.bind(&rest.identifer, Value::list(parameters.collect()))?;
+        }
+
+        let environment = self.allocate(namespace)?;
+        self.namespaces.push(environment);
+
+        let mut result = Ok(Value::None);
+
+        for expr in source.body.iter() {
+            result = expr.evaluate(self);
+            if result.is_err() {
+                break;
+            }
+        }
+
+        self.namespaces.pop();
+
+        result
+    }
+}
+
+impl Default for Isolate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Configures an isolate: which native modules it gets, its limits, and the isolate whose
+/// definitions it starts with. By default, an isolate only has the core library and the
+/// prelude, so it can compute but can't touch the world outside it.
+///
+/// ```
+/// use rlisp::builtins::Module;
+/// use rlisp::isolate::IsolateBuilder;
+///
+/// let prepared = IsolateBuilder::new().build();
+/// let minimal = IsolateBuilder::new().without_prelude().build();
+/// let untrusted = IsolateBuilder::new().prototype(&prepared).build();
+/// let timed = IsolateBuilder::new().module(Module::Time).prototype(&prepared).build();
+/// ```
+#[derive(Default)]
+pub struct IsolateBuilder {
+    modules: HashSet<Module>,
+    limits: Limits,
+    prototype: Option<Prototype>,
+    skip_prelude: bool,
+}
+
+/// What an isolate built from a prototype starts with.
+struct Prototype {
+    root: Environment,
+    global: Environment,
+    macros: HashSet<String>,
+    sources: Vec<(String, Rc<str>)>,
+}
+
+impl IsolateBuilder {
+    pub fn new() -> IsolateBuilder {
+        IsolateBuilder::default()
+    }
+
+    pub fn module(mut self, module: Module) -> IsolateBuilder {
+        self.modules.insert(module);
+        self
+    }
+
+    pub fn all_modules(mut self) -> IsolateBuilder {
+        self.modules.extend(Module::ALL);
+        self
+    }
+
+    pub fn limits(mut self, limits: Limits) -> IsolateBuilder {
+        self.limits = limits;
+        self
+    }
+
+    /// Leaves out the prelude, so that the isolate only has natives. Isolates made from a
+    /// prototype inherit its prelude, if it has one, either way.
+    pub fn without_prelude(mut self) -> IsolateBuilder {
+        self.skip_prelude = true;
+        self
+    }
+
+    /// Starts the isolate with the definitions of `isolate`, e.g. a loaded library, so that
+    /// they are evaluated once for any number of isolates. Definitions made by the new isolate
+    /// shadow the inherited ones.
+    ///
+    /// The environments, vectors and hash tables the definitions refer to are copied when the
+    /// isolate is built, so isolates built from the same prototype can't change what the others
+    /// see. Ports are shared. Natives come from the modules given to this builder: the ones
+    /// `isolate` has besides them aren't inherited, and calling one it kept elsewhere, e.g. in a
+    /// vector, fails.
+    ///
+    /// ```
+    /// use rlisp::builtins::Module;
+    /// use rlisp::evaluate::Evaluatable;
+    /// use rlisp::isolate::{Isolate, IsolateBuilder};
+    /// use rlisp::library;
+    /// use rlisp::value::Value;
+    ///
+    /// fn run(isolate: &mut Isolate, code: &str) {
+    ///     let program = library::parse(isolate, "code", code).unwrap();
+    ///     program.evaluate(isolate).unwrap();
+    /// }
+    ///
+    /// let mut prepared = IsolateBuilder::new().module(Module::Fs).build();
+    /// run(&mut prepared, "(define config (make-hash-table)) (hash-set! config 'mode 'safe)");
+    ///
+    /// let mut a = IsolateBuilder::new().prototype(&prepared).build();
+    /// let mut b = IsolateBuilder::new().prototype(&prepared).build();
+    /// run(&mut a, "(hash-set! config 'mode 'changed)");
+    /// run(&mut b, "(define mode (hash-ref config 'mode))");
+    /// assert_eq!(b.resolve(&"mode".into()), Some(Value::Symbol("safe".into())));
+    ///
+    /// // `b` wasn't given the fs module.
+    /// assert!(b.resolve(&"open-input-file".into()).is_none());
+    /// ```
+    pub fn prototype(mut self, isolate: &Isolate) -> IsolateBuilder {
+        self.prototype = Some(Prototype {
+            root: isolate.root.clone(),
+            global: isolate.global(),
+            macros: isolate.macros.clone(),
+            sources: isolate
+                .sources
+                .iter()
+                .map(|source| (source.name.clone(), source.code.clone()))
+                .collect(),
+        });
+        self
+    }
+
+    pub fn build(self) -> Isolate {
+        let prelude = !self.skip_prelude && self.prototype.is_none();
+
+        let mut natives = Namespace::new();
+        builtins::install(&mut natives);
+
+        for module in self.modules.iter() {
+            module.install(&mut natives);
+        }
+
+        let natives = Rc::new(RefCell::new(natives));
+        let mut heap = Heap::new();
+        let mut sources = Sources::default();
+
+        let (root, macros) = match self.prototype {
+            Some(prototype) => {
+                // Added in the same order, so that the copied code keeps its source ids.
+                for (name, code) in prototype.sources.iter() {
+                    sources.add(name, code);
+                }
+
+                let mut copier = Copier::new(&mut heap, &prototype.root, &natives);
+                let inherited = copier.environment(&prototype.global);
+                let root = Rc::new(RefCell::new(Namespace::with_parent(inherited)));
+                heap.track(&Object::Environment(natives));
+                (root, prototype.macros)
+            }
+            None => (natives, HashSet::new()),
+        };
+
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+
+        let mut isolate = Isolate {
+            stack: vec![],
+            namespaces: vec![],
+            root: root.clone(),
+            libraries: Libraries::default(),
+            sources,
+            macros,
+            handlers: vec![],
+            input: InputPort::stdin(),
+            output: OutputPort::stdout(),
+            heap,
+            limits: Limits::default(),
+            steps: 0,
+            depth: 0,
+            // xorshift gets stuck at zero.
+            random_state: seed | 1,
+            command_line: vec![],
+        };
+
+        isolate.heap.track(&Object::Environment(root.clone()));
+        let global = isolate.allocate(Namespace::with_parent(root)).unwrap();
+        isolate.namespaces.push(global);
+
+        if prelude {
+            prelude::install(&mut isolate).expect("the prelude is valid. ");
+        }
+
+        // Only set now, so that the environments above and the prelude count towards `max_heap`
+        // but are never refused.
+        isolate.limits = self.limits;
+        isolate
+    }
+}
+
+/// Copies what a prototype's global environment refers to into a new isolate, keeping objects
+/// that are shared, or refer to themselves, that way.
+struct Copier<'a> {
+    heap: &'a mut Heap,
+    /// The new isolate's natives, which also get what the prototype's root has besides natives.
+    natives: Environment,
+    environments: HashMap<*const RefCell<Namespace>, Environment>,
+    /// Copies of vectors and hash tables, by the address of the original.
+    objects: HashMap<*const (), Value>,
+    pairs: HashMap<*const Pair, Value>,
+}
+
+impl<'a> Copier<'a> {
+    fn new(heap: &'a mut Heap, root: &Environment, natives: &Environment) -> Copier<'a> {
+        let mut copier = Copier {
+            heap,
+            natives: natives.clone(),
+            environments: HashMap::new(),
+            objects: HashMap::new(),
+            pairs: HashMap::new(),
+        };
+        copier.environments.insert(Rc::as_ptr(root), natives.clone());
+
+        // Natives bound under their own name are left out: the new isolate has its own.
+        let definitions: Vec<_> = root
+            .borrow()
+            .variables
+            .iter()
+            .filter(|(name, value)| {
+                !matches!(value, Value::NativeThunk(native) if native.name == name.as_str())
+            })
+            .map(|(name, value)| (name.clone(), copier.value(value)))
+            .collect();
+        natives.borrow_mut().variables.extend(definitions);
+
+        copier
+    }
+
+    fn environment(&mut self, environment: &Environment) -> Environment {
+        if let Some(copy) = self.environments.get(&Rc::as_ptr(environment)) {
+            return copy.clone();
+        }
+
+        let copy = Rc::new(RefCell::new(Namespace::new()));
+        self.environments.insert(Rc::as_ptr(environment), copy.clone());
+        self.heap.track(&Object::Environment(copy.clone()));
+
+        let namespace = environment.borrow();
+        let parent = namespace.parent.as_ref().map(|parent| self.environment(parent));
+        let variables = namespace
+            .variables
+            .iter()
+            .map(|(name, value)| (name.clone(), self.value(value)))
+            .collect();
+        *copy.borrow_mut() = Namespace { variables, parent };
+
+        copy
+    }
+
+    fn value(&mut self, value: &Value) -> Value {
+        match value {
+            Value::Pair(_) => self.list(value),
+            Value::Thunk(thunk) => Value::Thunk(self.thunk(thunk)),
+            Value::Macro(thunk) => Value::Macro(self.thunk(thunk)),
+            Value::NativeThunk(native) => {
+                let installed = self.natives.borrow().variables.get(native.name).cloned();
+
+                match installed {
+                    Some(Value::NativeThunk(installed)) if installed.name == native.name => {
+                        Value::NativeThunk(installed)
+                    }
+                    _ => Value::NativeThunk(NativeThunk {
+                        name: native.name,
+                        function: builtins::not_installed,
+                    }),
+                }
+            }
+            Value::Environment(environment) => Value::Environment(self.environment(environment)),
+            Value::Error(error) => Value::Error(Rc::new(ErrorObject {
+                kind: error.kind.clone(),
+                message: error.message.clone(),
+                irritants: error.irritants.iter().map(|value| self.value(value)).collect(),
+            })),
+            Value::Vector(items) => {
+                if let Some(copy) = self.objects.get(&(Rc::as_ptr(items) as *const ())) {
+                    return copy.clone();
+                }
+
+                let copy = Rc::new(RefCell::new(vec![]));
+                self.objects.insert(Rc::as_ptr(items) as *const (), Value::Vector(copy.clone()));
+                self.heap.track(&Object::Vector(copy.clone()));
+
+                let elements = items.borrow().iter().map(|item| self.value(item)).collect();
+                *copy.borrow_mut() = elements;
+                Value::Vector(copy)
+            }
+            Value::HashTable(table) => {
+                if let Some(copy) = self.objects.get(&(Rc::as_ptr(table) as *const ())) {
+                    return copy.clone();
+                }
+
+                let copy = Rc::new(RefCell::new(HashMap::new()));
+                self.objects.insert(Rc::as_ptr(table) as *const (), Value::HashTable(copy.clone()));
+                self.heap.track(&Object::HashTable(copy.clone()));
+
+                let entries: Vec<_> = table
+                    .borrow()
+                    .iter()
+                    .map(|(key, value)| (self.value(key), self.value(value)))
+                    .collect();
+                copy.borrow_mut().extend(entries);
+                Value::HashTable(copy)
+            }
+            _ => value.clone(),
+        }
+    }
+
+    fn thunk(&mut self, thunk: &Thunk) -> Thunk {
+        Thunk {
+            source: thunk.source.clone(),
+            closure: self.environment(&thunk.closure),
+        }
+    }
+
+    /// Copies the pairs of a list in a loop, so that long lists don't nest deeply.
+    fn list(&mut self, list: &Value) -> Value {
+        let mut pairs = vec![];
+        let mut cur = list;
+
+        let tail = loop {
+            match cur {
+                Value::Pair(pair) => match self.pairs.get(&Rc::as_ptr(pair)) {
+                    Some(copy) => break copy.clone(),
+                    None => {
+                        pairs.push(pair);
+                        cur = &pair.cdr;
+                    }
+                },
+                _ => break self.value(cur),
+            }
+        };
+
+        pairs.into_iter().rev().fold(tail, |cdr, pair| {
+            let copy = Value::cons(self.value(&pair.car), cdr);
+            self.pairs.insert(Rc::as_ptr(pair), copy.clone());
+            copy
+        })
+    }
 }
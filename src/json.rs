@@ -0,0 +1,484 @@
+use crate::isolate::{Isolate, RuntimeError};
+use crate::value::Value;
+use std::fmt;
+use std::rc::Rc;
+
+/// Arrays and objects nested deeper than this aren't parsed, so that input can't overflow the
+/// stack.
+const MAX_DEPTH: usize = 512;
+
+/// A JSON document, for exchanging data with scripts.
+///
+/// In rlisp, objects are hash tables keyed by strings, arrays are vectors, `null` is the symbol
+/// `null`, and numbers are integers. Converting the other way also accepts lists as arrays, and
+/// symbols as strings, e.g. as object keys.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Boolean(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    /// Members in the order they were written.
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn parse(text: &str) -> Result<Json, RuntimeError> {
+        Json::parse_with(text, false)
+    }
+
+    /// Parses `text` into a value of `isolate`. Numbers that aren't integers are reported where
+    /// they are, since rlisp has no others.
+    pub fn read(text: &str, isolate: &mut Isolate) -> Result<Value, RuntimeError> {
+        Json::parse_with(text, true)?.to_value(isolate)
+    }
+
+    fn parse_with(text: &str, integers_only: bool) -> Result<Json, RuntimeError> {
+        let mut parser = JsonParser {
+            chars: text.chars().collect(),
+            position: 0,
+            depth: 0,
+            integers_only,
+        };
+
+        let json = parser.value()?;
+        parser.whitespace();
+
+        if parser.position < parser.chars.len() {
+            return Err(parser.error("expecting the end of the input"));
+        }
+
+        Ok(json)
+    }
+
+    /// Creates the rlisp value for this document in `isolate`.
+    pub fn to_value(&self, isolate: &mut Isolate) -> Result<Value, RuntimeError> {
+        match self {
+            Json::Null => Ok(Value::Symbol(String::from("null"))),
+            Json::Boolean(value) => Ok(Value::Boolean(*value)),
+            Json::Number(number) => {
+                if is_integer(*number) {
+                    Ok(Value::Integer(*number as i32))
+                } else {
+                    Err(RuntimeError::TypeMismatch {
+                        name: String::from("json->value"),
+                        expected: format!("integers, not {}", number),
+                    })
+                }
+            }
+            Json::String(value) => Ok(Value::String(value.clone())),
+            Json::Array(items) => {
+                let items = items
+                    .iter()
+                    .map(|item| item.to_value(isolate))
+                    .collect::<Result<_, _>>()?;
+                isolate.allocate_vector(items)
+            }
+            Json::Object(members) => {
+                let table = isolate.allocate_hash_table()?;
+
+                if let Value::HashTable(entries) = &table {
+                    for (key, value) in members.iter() {
+                        let value = value.to_value(isolate)?;
+                        entries
+                            .borrow_mut()
+                            .insert(Value::String(key.clone()), value);
+                    }
+                }
+
+                Ok(table)
+            }
+        }
+    }
+
+    pub fn from_value(value: &Value) -> Result<Json, RuntimeError> {
+        from_value(value, &mut vec![])
+    }
+}
+
+/// `converting` holds the vectors and hash tables being converted, since they may contain
+/// themselves.
+fn from_value(value: &Value, converting: &mut Vec<*const ()>) -> Result<Json, RuntimeError> {
+    let unserializable = || RuntimeError::Unserializable {
+        value: value.written().to_string(),
+        format: String::from("JSON"),
+    };
+
+    let address = match value {
+        Value::Vector(items) => Some(Rc::as_ptr(items) as *const ()),
+        Value::HashTable(table) => Some(Rc::as_ptr(table) as *const ()),
+        _ => None,
+    };
+
+    if let Some(address) = address {
+        if converting.contains(&address) {
+            return Err(unserializable());
+        }
+        converting.push(address);
+    }
+
+    let json = match value {
+        Value::Symbol(name) if name == "null" => Json::Null,
+        Value::Symbol(name) | Value::String(name) => Json::String(name.clone()),
+        Value::Boolean(value) => Json::Boolean(*value),
+        Value::Integer(value) => Json::Number(*value as f64),
+        Value::Nil | Value::Pair(_) => {
+            let items = value.to_vec().ok_or_else(unserializable)?;
+            Json::Array(
+                items
+                    .iter()
+                    .map(|item| from_value(item, converting))
+                    .collect::<Result<_, _>>()?,
+            )
+        }
+        Value::Vector(items) => Json::Array(
+            items
+                .borrow()
+                .iter()
+                .map(|item| from_value(item, converting))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::HashTable(table) => {
+            let mut members = vec![];
+
+            for (key, value) in table.borrow().iter() {
+                let key = match key {
+                    Value::String(key) | Value::Symbol(key) => key.clone(),
+                    _ => {
+                        return Err(RuntimeError::Unserializable {
+                            value: format!("the key {}", key.written()),
+                            format: String::from("JSON"),
+                        })
+                    }
+                };
+                members.push((key, from_value(value, converting)?));
+            }
+
+            // Hash tables have no order, so members are sorted to make the output stable.
+            members.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Json::Object(members)
+        }
+        _ => return Err(unserializable()),
+    };
+
+    if address.is_some() {
+        converting.pop();
+    }
+
+    Ok(json)
+}
+
+/// Writes the document compactly, on one line.
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Boolean(value) => write!(f, "{}", value),
+            Json::Number(number) => write!(f, "{}", number),
+            Json::String(value) => write_string(f, value),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(members) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, value: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in value.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            '\u{8}' => write!(f, "\\b")?,
+            '\u{c}' => write!(f, "\\f")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+fn is_integer(number: f64) -> bool {
+    number.fract() == 0.0 && number >= i32::MIN as f64 && number <= i32::MAX as f64
+}
+
+struct JsonParser {
+    chars: Vec<char>,
+    position: usize,
+    depth: usize,
+    /// Whether numbers must fit in an rlisp integer.
+    integers_only: bool,
+}
+
+impl JsonParser {
+    fn error(&self, message: &str) -> RuntimeError {
+        RuntimeError::InvalidJson {
+            message: String::from(message),
+            offset: self.position,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn whitespace(&mut self) {
+        while let Some(' ' | '\t' | '\r' | '\n') = self.peek() {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), RuntimeError> {
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expecting '{}'", expected)))
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str, json: Json) -> Result<Json, RuntimeError> {
+        for expected in keyword.chars() {
+            if self.peek() != Some(expected) {
+                return Err(self.error(&format!("expecting {}", keyword)));
+            }
+            self.position += 1;
+        }
+
+        Ok(json)
+    }
+
+    fn value(&mut self) -> Result<Json, RuntimeError> {
+        self.whitespace();
+
+        match self.peek() {
+            Some('n') => self.keyword("null", Json::Null),
+            Some('t') => self.keyword("true", Json::Boolean(true)),
+            Some('f') => self.keyword("false", Json::Boolean(false)),
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some('-' | '0'..='9') => self.number(),
+            Some('[') => self.nested(Self::array),
+            Some('{') => self.nested(Self::object),
+            Some(_) => Err(self.error("expecting a value")),
+            None => Err(self.error("unexpected end of the input")),
+        }
+    }
+
+    fn nested(
+        &mut self,
+        parse: fn(&mut Self) -> Result<Json, RuntimeError>,
+    ) -> Result<Json, RuntimeError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error("nested too deeply"));
+        }
+
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn array(&mut self) -> Result<Json, RuntimeError> {
+        self.expect('[')?;
+        let mut items = vec![];
+
+        self.whitespace();
+        if self.peek() == Some(']') {
+            self.position += 1;
+            return Ok(Json::Array(items));
+        }
+
+        loop {
+            items.push(self.value()?);
+            self.whitespace();
+
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some(']') => {
+                    self.position += 1;
+                    return Ok(Json::Array(items));
+                }
+                _ => return Err(self.error("expecting ',' or ']'")),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, RuntimeError> {
+        self.expect('{')?;
+        let mut members = vec![];
+
+        self.whitespace();
+        if self.peek() == Some('}') {
+            self.position += 1;
+            return Ok(Json::Object(members));
+        }
+
+        loop {
+            self.whitespace();
+            let key = self.string()?;
+            self.whitespace();
+            self.expect(':')?;
+            members.push((key, self.value()?));
+            self.whitespace();
+
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some('}') => {
+                    self.position += 1;
+                    return Ok(Json::Object(members));
+                }
+                _ => return Err(self.error("expecting ',' or '}'")),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, RuntimeError> {
+        let start = self.position;
+
+        if self.peek() == Some('-') {
+            self.position += 1;
+        }
+
+        let digits = |parser: &mut Self| {
+            let start = parser.position;
+            while let Some('0'..='9') = parser.peek() {
+                parser.position += 1;
+            }
+            parser.position > start
+        };
+
+        if !digits(self) {
+            return Err(self.error("expecting digits"));
+        }
+
+        if self.peek() == Some('.') {
+            self.position += 1;
+            if !digits(self) {
+                return Err(self.error("expecting digits after '.'"));
+            }
+        }
+
+        if let Some('e' | 'E') = self.peek() {
+            self.position += 1;
+            if let Some('+' | '-') = self.peek() {
+                self.position += 1;
+            }
+            if !digits(self) {
+                return Err(self.error("expecting digits in the exponent"));
+            }
+        }
+
+        let text: String = self.chars[start..self.position].iter().collect();
+        let number: f64 = text.parse().map_err(|_| self.error("malformed number"))?;
+
+        if self.integers_only && !is_integer(number) {
+            self.position = start;
+            return Err(self.error(&format!("{} isn't an integer", text)));
+        }
+
+        Ok(Json::Number(number))
+    }
+
+    fn string(&mut self) -> Result<String, RuntimeError> {
+        self.expect('"')?;
+        let mut string = String::new();
+
+        loop {
+            match self.peek() {
+                Some('"') => {
+                    self.position += 1;
+                    return Ok(string);
+                }
+                Some('\\') => {
+                    self.position += 1;
+                    let c = match self.peek() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            self.position += 1;
+                            string.push(self.unicode_escape()?);
+                            continue;
+                        }
+                        _ => return Err(self.error("unknown escape")),
+                    };
+                    self.position += 1;
+                    string.push(c);
+                }
+                Some(c) if (c as u32) < 0x20 => {
+                    return Err(self.error("control characters must be escaped"))
+                }
+                Some(c) => {
+                    self.position += 1;
+                    string.push(c);
+                }
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, RuntimeError> {
+        let mut code = 0;
+
+        for _ in 0..4 {
+            let digit = self
+                .peek()
+                .and_then(|c| c.to_digit(16))
+                .ok_or_else(|| self.error("expecting 4 hexadecimal digits"))?;
+            code = code * 16 + digit;
+            self.position += 1;
+        }
+
+        Ok(code)
+    }
+
+    /// Reads the digits of `\uXXXX`, and the low surrogate following a high one.
+    fn unicode_escape(&mut self) -> Result<char, RuntimeError> {
+        let high = self.hex4()?;
+
+        let code = if (0xd800..0xdc00).contains(&high) {
+            if self.peek() != Some('\\') || self.chars.get(self.position + 1) != Some(&'u') {
+                return Err(self.error("expecting a low surrogate"));
+            }
+            self.position += 2;
+
+            let low = self.hex4()?;
+            if !(0xdc00..0xe000).contains(&low) {
+                return Err(self.error("expecting a low surrogate"));
+            }
+
+            0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+        } else {
+            high
+        };
+
+        char::from_u32(code).ok_or_else(|| self.error("invalid code point"))
+    }
+}
@@ -10,11 +10,14 @@ pub struct Lexer<'a> {
     char_indices: str::CharIndices<'a>,
 }
 
+/// Offsets in the lexer count chars, not bytes, since that is what ariadne expects.
 #[derive(fmt::Debug)]
 pub struct LexicalError {
     pub offset: i32,
     pub row: i32,
     pub col: i32,
+    /// Offset just past the text in error.
+    pub end_offset: i32,
     pub message: String,
 }
 
@@ -32,9 +35,17 @@ impl fmt::Display for LexicalError {
 pub enum TokenTag {
     LParen,
     RParen,
+    /// `#(`, which starts a vector.
+    VectorStart,
     Identifier(String),
     IntegerLiteral(i32),
     StringLiteral(String),
+    BooleanLiteral(bool),
+    Quote,
+    Quasiquote,
+    Unquote,
+    UnquoteSplicing,
+    Dot,
     EOF,
 }
 
@@ -44,6 +55,8 @@ pub struct Token {
     pub offset: i32,
     pub row: i32,
     pub col: i32,
+    /// Offset just past the last char of the token.
+    pub end_offset: i32,
 }
 
 impl<'a> Lexer<'a> {
@@ -60,7 +73,20 @@ impl<'a> Lexer<'a> {
 
     pub fn init(&mut self) -> Result<Token, LexicalError> {
         self.next_char();
-        return self.next();
+
+        // A `#!` line lets scripts be run directly, e.g. with `#!/usr/bin/env rlisp`.
+        if self.code.starts_with("#!") {
+            while !matches!(self.cur, Some('\n') | None) {
+                self.next_char();
+            }
+        }
+
+        self.next()
+    }
+
+    /// Whether every character of the code has been consumed.
+    pub fn is_at_end(&self) -> bool {
+        self.cur.is_none()
     }
 
     fn next_char(&mut self) {
@@ -77,21 +103,48 @@ impl<'a> Lexer<'a> {
                 }
             }
             None => {
+                // Past the end, the offset is the number of chars in the code.
+                if self.cur.is_some() || self.cur_offset < 0 {
+                    self.cur_offset += 1;
+                }
                 self.cur = None;
             }
         }
     }
 
+    fn punctuation(&mut self, tag: TokenTag) -> Token {
+        let token = Token {
+            tag,
+            offset: self.cur_offset,
+            row: self.cur_row,
+            col: self.cur_col,
+            end_offset: self.cur_offset + 1,
+        };
+        self.next_char();
+        token
+    }
+
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Result<Token, LexicalError> {
-        loop {
-            match self.cur {
-                Some(' ' | '\t' | '\r' | '\n') => {
-                    self.next_char();
-                }
-                _ => {
-                    break;
-                }
-            }
+        let result = self.lex();
+        let end_offset = self.cur_offset;
+
+        match result {
+            Ok(token) => Ok(Token {
+                end_offset: end_offset.max(token.offset),
+                ..token
+            }),
+            Err(error) => Err(LexicalError {
+                end_offset: end_offset.max(error.offset + 1),
+                ..error
+            }),
+        }
+    }
+
+    /// Lexes the next token. `end_offset` is filled in by `next`, once the token is complete.
+    fn lex(&mut self) -> Result<Token, LexicalError> {
+        while let Some(' ' | '\t' | '\r' | '\n') = self.cur {
+            self.next_char();
         }
 
         let mut token = Ok(Token {
@@ -99,35 +152,77 @@ impl<'a> Lexer<'a> {
             offset: self.cur_offset,
             row: self.cur_row,
             col: self.cur_col,
+            end_offset: self.cur_offset,
         });
 
         match self.cur {
             Some('(') => {
-                token = Ok(Token {
-                    tag: TokenTag::LParen,
-                    offset: self.cur_offset,
-                    row: self.cur_row,
-                    col: self.cur_col,
-                });
-                self.next_char();
+                token = Ok(self.punctuation(TokenTag::LParen));
             }
             Some(')') => {
+                token = Ok(self.punctuation(TokenTag::RParen));
+            }
+            Some('\'') => {
+                token = Ok(self.punctuation(TokenTag::Quote));
+            }
+            Some('`') => {
+                token = Ok(self.punctuation(TokenTag::Quasiquote));
+            }
+            Some(',') => {
+                let unquote = self.punctuation(TokenTag::Unquote);
+
+                if let Some('@') = self.cur {
+                    self.next_char();
+                    token = Ok(Token {
+                        tag: TokenTag::UnquoteSplicing,
+                        ..unquote
+                    });
+                } else {
+                    token = Ok(unquote);
+                }
+            }
+            Some('.') => {
+                token = Ok(self.punctuation(TokenTag::Dot));
+            }
+            Some('#') => {
+                let offset = self.cur_offset;
+                let col = self.cur_col;
+                let row = self.cur_row;
+
+                self.next_char();
+
+                let tag = match self.cur {
+                    Some('t') => TokenTag::BooleanLiteral(true),
+                    Some('f') => TokenTag::BooleanLiteral(false),
+                    Some('(') => TokenTag::VectorStart,
+                    _ => {
+                        return Err(LexicalError {
+                            offset,
+                            col,
+                            row,
+                            message: "unknown '#' syntax".to_string(),
+                            end_offset: offset,
+                        })
+                    }
+                };
+                self.next_char();
+
                 token = Ok(Token {
-                    tag: TokenTag::RParen,
-                    offset: self.cur_offset,
-                    row: self.cur_row,
-                    col: self.cur_col,
+                    tag,
+                    offset,
+                    row,
+                    col,
+                    end_offset: offset,
                 });
-                self.next_char();
             }
-            Some('A'..='Z' | 'a'..='z') => {
+            Some(c) if is_identifier_initial(c) => {
                 let mut identifier = String::new();
                 let offset = self.cur_offset;
                 let col = self.cur_col;
                 let row = self.cur_row;
                 loop {
                     match self.cur {
-                        Some(c @ ('A'..='Z' | 'a'..='z' | '0'..='9' | '-')) => {
+                        Some(c) if is_identifier_initial(c) || c.is_ascii_digit() || c == '.' => {
                             identifier.push(c);
                             self.next_char();
                         }
@@ -137,6 +232,7 @@ impl<'a> Lexer<'a> {
                                 offset,
                                 row,
                                 col,
+                                end_offset: offset,
                             });
                             break;
                         }
@@ -164,6 +260,7 @@ impl<'a> Lexer<'a> {
                                     col,
                                     row,
                                     message: e.to_string(),
+                                    end_offset: offset,
                                 });
                             } else {
                                 token = Ok(Token {
@@ -171,6 +268,7 @@ impl<'a> Lexer<'a> {
                                     offset,
                                     row,
                                     col,
+                                    end_offset: offset,
                                 });
                             }
 
@@ -185,6 +283,8 @@ impl<'a> Lexer<'a> {
                 let offset = self.cur_offset;
                 let col = self.cur_col;
                 let row = self.cur_row;
+                // Reported once the whole string is consumed, so that lexing resumes after it.
+                let mut escape_error = None;
 
                 self.next_char();
 
@@ -198,20 +298,23 @@ impl<'a> Lexer<'a> {
                                 Some('r') => string.push('\r'),
                                 Some('t') => string.push('\t'),
                                 Some('"') => string.push('"'),
+                                Some('\\') => string.push('\\'),
                                 None => {
                                     return Err(LexicalError {
                                         offset,
                                         col,
                                         row,
                                         message: "unexpected EOF when parsing string".to_string(),
+                                        end_offset: offset,
                                     })
                                 }
                                 _ => {
-                                    return Err(LexicalError {
+                                    escape_error = Some(LexicalError {
                                         offset,
                                         col,
                                         row,
                                         message: "unknown escaped character".to_string(),
+                                        end_offset: offset,
                                     })
                                 }
                             }
@@ -220,11 +323,16 @@ impl<'a> Lexer<'a> {
                         Some('"') => {
                             self.next_char();
 
+                            if let Some(error) = escape_error {
+                                return Err(error);
+                            }
+
                             return Ok(Token {
                                 tag: TokenTag::StringLiteral(string),
                                 offset,
                                 col,
                                 row,
+                                end_offset: offset,
                             })
                         }
                         Some(c) => {
@@ -237,15 +345,33 @@ impl<'a> Lexer<'a> {
                                 col,
                                 row,
                                 message: "unexpected EOF when parsing string".to_string(),
+                                end_offset: offset,
                             })
                         }
 
                     }
                 }
             }
-            _ => {}
+            Some(c) => {
+                let error = LexicalError {
+                    offset: self.cur_offset,
+                    col: self.cur_col,
+                    row: self.cur_row,
+                    message: format!("unexpected character {:?}", c),
+                    end_offset: self.cur_offset + 1,
+                };
+                // Consumed, so that lexing resumes after it.
+                self.next_char();
+
+                return Err(error);
+            }
+            None => {}
         }
 
-        return token;
+        token
     }
 }
+
+fn is_identifier_initial(c: char) -> bool {
+    c.is_ascii_alphabetic() || "!$%&*/:<=>?^_~+-".contains(c)
+}
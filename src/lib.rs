@@ -1,6 +1,17 @@
 pub mod lexer;
 pub mod parser;
+pub mod reader;
+pub mod analyzer;
 pub mod ast;
 pub mod evaluate;
 pub mod isolate;
+pub mod gc;
+pub mod json;
+pub mod library;
+pub mod prelude;
+pub mod printer;
+pub mod snapshot;
+pub mod source;
+pub mod port;
 pub mod value;
+pub mod builtins;
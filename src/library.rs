@@ -0,0 +1,164 @@
+use crate::ast::Program;
+use crate::evaluate::Evaluatable;
+use crate::isolate::{Environment, Isolate, Namespace, RuntimeError};
+use crate::parser::Parser;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// A file loaded by `require`. It is evaluated once, in an environment of its own, and only the
+/// names it exports are bound by `require`.
+#[derive(Debug)]
+pub struct Library {
+    pub path: PathBuf,
+    pub environment: Environment,
+    pub exports: Vec<String>,
+}
+
+/// The libraries an isolate has loaded, and where it looks for more.
+#[derive(Debug, Default)]
+pub struct Libraries {
+    /// Directories searched for libraries that aren't found next to the file requiring them.
+    pub search_path: Vec<PathBuf>,
+    loaded: HashMap<PathBuf, Rc<Library>>,
+    /// Files being evaluated, the innermost one last, with the names they have exported so far.
+    loading: Vec<(PathBuf, Vec<String>)>,
+}
+
+impl Libraries {
+    /// Marks `path` as being evaluated, so that libraries it requires are resolved relative to
+    /// it and requiring it again is reported as a cycle.
+    pub fn enter(&mut self, path: &Path) {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        self.loading.push((path, vec![]));
+    }
+
+    /// Stops evaluating the innermost file, returning the names it exported.
+    pub fn leave(&mut self) -> Vec<String> {
+        self.loading
+            .pop()
+            .map(|(_, exports)| exports)
+            .unwrap_or_default()
+    }
+
+    pub fn export(&mut self, names: impl Iterator<Item = String>) {
+        // Outside of any file, e.g. in code evaluated by an embedder, there is nobody to export
+        // to.
+        if let Some((_, exports)) = self.loading.last_mut() {
+            exports.extend(names);
+        }
+    }
+
+    /// The directory of the innermost file being evaluated, against which relative paths are
+    /// resolved.
+    pub fn base(&self) -> PathBuf {
+        match self.loading.last() {
+            Some((path, _)) => path.parent().map(Path::to_path_buf).unwrap_or_default(),
+            None => PathBuf::new(),
+        }
+    }
+
+    /// Finds the file `name` refers to. Names starting with `./` or `../` are only looked up
+    /// relative to the requiring file; other names are then looked up in the search path. The
+    /// `.rl` extension may be left out.
+    pub fn resolve(&self, name: &str) -> Option<PathBuf> {
+        let mut directories = vec![self.base()];
+
+        if !name.starts_with("./") && !name.starts_with("../") {
+            directories.extend(self.search_path.iter().cloned());
+        }
+
+        directories.iter().find_map(|directory| {
+            let path = directory.join(name);
+
+            [path.clone(), path.with_extension("rl")]
+                .into_iter()
+                .find(|candidate| candidate.is_file())
+                .and_then(|candidate| fs::canonicalize(candidate).ok())
+        })
+    }
+}
+
+/// Registers `code` with the isolate under `name` and parses it.
+pub fn parse(isolate: &mut Isolate, name: &str, code: &str) -> Result<Program, RuntimeError> {
+    let source = isolate.sources.add(name, code);
+    let mut parser = Parser::with_source(code, source);
+    parser.analyzer.macros = isolate.macros.clone();
+
+    parser
+        .parse()
+        .map_err(|errors| RuntimeError::Unparsable { source, errors })
+}
+
+pub fn read_file(path: &Path) -> Result<String, RuntimeError> {
+    fs::read_to_string(path).map_err(|error| RuntimeError::Io {
+        path: path.display().to_string(),
+        message: error.to_string(),
+    })
+}
+
+/// Evaluates `program`, read from `path`, in `environment`. Returns the names it exported.
+pub fn evaluate_file(
+    isolate: &mut Isolate,
+    path: &Path,
+    program: &Program,
+    environment: Environment,
+) -> Result<Vec<String>, RuntimeError> {
+    isolate.libraries.enter(path);
+    isolate.namespaces.push(environment);
+    let result = program.evaluate(isolate);
+    isolate.namespaces.pop();
+    let exports = isolate.libraries.leave();
+
+    result.map(|_| exports)
+}
+
+/// Returns the library `name` refers to, loading it unless the isolate already has.
+pub fn require(isolate: &mut Isolate, name: &str) -> Result<Rc<Library>, RuntimeError> {
+    let path = isolate
+        .libraries
+        .resolve(name)
+        .ok_or_else(|| RuntimeError::LibraryNotFound {
+            name: String::from(name),
+        })?;
+
+    if let Some(library) = isolate.libraries.loaded.get(&path) {
+        return Ok(library.clone());
+    }
+
+    let loading = &isolate.libraries.loading;
+
+    if let Some(start) = loading.iter().position(|(loading, _)| *loading == path) {
+        let mut cycle: Vec<String> = loading[start..]
+            .iter()
+            .map(|(path, _)| path.display().to_string())
+            .collect();
+        cycle.push(path.display().to_string());
+
+        return Err(RuntimeError::ImportCycle { cycle });
+    }
+
+    let code = read_file(&path)?;
+    let program = parse(isolate, &path.display().to_string(), &code)?;
+    let environment = isolate.allocate(Namespace::with_parent(isolate.root.clone()))?;
+    let exports = evaluate_file(isolate, &path, &program, environment.clone())?;
+
+    for export in exports.iter() {
+        if !environment.borrow().variables.contains_key(export) {
+            return Err(RuntimeError::Unbound {
+                name: export.clone(),
+            });
+        }
+    }
+
+    let library = Rc::new(Library {
+        path: path.clone(),
+        environment,
+        exports,
+    });
+
+    isolate.libraries.loaded.insert(path, library.clone());
+
+    Ok(library)
+}
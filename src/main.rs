@@ -1,60 +1,457 @@
 use std::env;
 use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
 
-use ariadne::{sources, Label, Report, ReportKind};
+use ariadne::{Label, Report, ReportKind};
+use rlisp::ast::Program;
 use rlisp::evaluate::Evaluatable;
 use rlisp::isolate;
+use rlisp::lexer::{Lexer, TokenTag};
+use rlisp::library;
 use rlisp::parser;
+use rlisp::printer::Printer;
+use rlisp::snapshot;
+use rlisp::source::{SourceId, Sources};
+
+/// Deep enough for ordinary programs, yet shallow enough not to overflow the native stack.
+const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+
+/// Errors raised deep in a recursion carry a frame per call; only the innermost are reported.
+const MAX_REPORTED_FRAMES: usize = 10;
+
+/// Something to run, in the order given on the command line.
+enum Input {
+    File(PathBuf),
+    /// Code given with `-e`.
+    Expression(String),
+    /// The program is read from standard input, when given `-` or when stdin is piped.
+    Stdin,
+}
+
+/// A stage of the pipeline to show, chosen with `--dump=`.
+#[derive(PartialEq)]
+enum Dump {
+    Tokens,
+    Ast,
+    /// The AST once the program has run, with the macro uses it reached expanded.
+    Expanded,
+}
+
+/// The stages to show. `--show-ast` is the same as `--dump=ast`.
+fn dumps(options: &[String]) -> Result<Vec<Dump>, String> {
+    let mut dumps = options
+        .iter()
+        .filter_map(|option| option.strip_prefix("--dump="))
+        .map(|stage| match stage {
+            "tokens" => Ok(Dump::Tokens),
+            "ast" => Ok(Dump::Ast),
+            "expanded" => Ok(Dump::Expanded),
+            "bytecode" => Err(String::from(
+                "there is no bytecode to dump: rlisp evaluates the AST directly.",
+            )),
+            _ => Err(format!(
+                "unknown stage {:?}: --dump expects tokens, ast or expanded.",
+                stage
+            )),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if options.iter().any(|s| s.as_str() == "--show-ast") && !dumps.contains(&Dump::Ast) {
+        dumps.push(Dump::Ast);
+    }
+
+    Ok(dumps)
+}
+
+/// Whether the file at `path` starts with a `#!` line.
+fn is_script(path: &Path) -> bool {
+    let mut start = [0; 2];
+    fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut start))
+        .is_ok()
+        && &start == b"#!"
+}
 
 fn main() {
     let args: Vec<String> = env::args().collect();
 
-    let show_ast = args.iter().find(| s | s.as_str() == "--show-ast").is_some();
+    let mut options = vec![];
+    let mut inputs = vec![];
+    // Arguments for the program, returned by `command-line`.
+    let mut arguments = vec![];
+    // Snapshots to start from and to save to once every input has run.
+    let mut snapshot = None;
+    let mut save_snapshot = None;
+    let mut rest = args.iter().skip(1);
+
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--" => {
+                arguments.extend(rest.by_ref().cloned());
+            }
+            "-e" => inputs.push(Input::Expression(
+                rest.next()
+                    .unwrap_or_else(|| usage_error("-e needs an expression."))
+                    .clone(),
+            )),
+            "-" => inputs.push(Input::Stdin),
+            "--snapshot" => {
+                snapshot = Some(PathBuf::from(
+                    rest.next()
+                        .unwrap_or_else(|| usage_error("--snapshot needs a file.")),
+                ))
+            }
+            "--save-snapshot" => {
+                save_snapshot =
+                    Some(PathBuf::from(rest.next().unwrap_or_else(|| {
+                        usage_error("--save-snapshot needs a file.")
+                    })))
+            }
+            option if option.starts_with("--") => options.push(arg.clone()),
+            path => {
+                inputs.push(Input::File(PathBuf::from(path)));
+
+                // Everything after a script run by its `#!` line is meant for the script.
+                if is_script(Path::new(path)) {
+                    arguments.extend(rest.by_ref().cloned());
+                }
+            }
+        }
+    }
+
+    if inputs.is_empty() {
+        if !io::stdin().is_terminal() {
+            inputs.push(Input::Stdin);
+        } else if save_snapshot.is_none() {
+            usage_error("need a path to the source file, -e with an expression, or - for stdin.");
+        }
+    }
+
+    let dumps = dumps(&options).unwrap_or_else(|message| usage_error(&message));
+
+    let mut builder = isolate::IsolateBuilder::new();
+
+    // A snapshot has the prelude already.
+    if snapshot.is_some() || options.iter().any(|s| s.as_str() == "--no-prelude") {
+        builder = builder.without_prelude();
+    }
+
+    let mut isolate = builder
+        .all_modules()
+        .limits(isolate::Limits {
+            fuel: numeric_flag(&options, "--fuel="),
+            max_call_depth: numeric_flag(&options, "--max-call-depth=")
+                .or(Some(DEFAULT_MAX_CALL_DEPTH)),
+            max_heap: numeric_flag(&options, "--max-heap="),
+        })
+        .build();
 
-    let contents = fs::read_to_string(args.get(1).expect("need a path to the source file. ").clone()).expect("Something went wrong reading the file");
+    if let Some(path) = &snapshot {
+        let result = fs::read(path)
+            .map_err(|error| isolate::RuntimeError::Io {
+                path: path.display().to_string(),
+                message: error.to_string(),
+            })
+            .and_then(|bytes| snapshot::restore(&mut isolate, &bytes));
 
-    let mut parser = parser::Parser::new(contents.as_str());
+        if let Err(error) = result {
+            report_runtime_error(error, &isolate.sources);
+            process::exit(1);
+        }
+    }
+
+    isolate.libraries.search_path = options
+        .iter()
+        .filter_map(|arg| arg.strip_prefix("--library-path="))
+        .map(PathBuf::from)
+        .collect();
 
-    parser.init().unwrap();
+    let program_name = match inputs.first() {
+        Some(Input::File(path)) => path.display().to_string(),
+        Some(Input::Stdin) => String::from("-"),
+        _ => args[0].clone(),
+    };
 
-    let result = parser.parse();
+    isolate.command_line = [program_name].into_iter().chain(arguments).collect();
 
-    match result {
-        Ok(ast) => {
-            if show_ast {
-                println!("{:#?}", ast);
+    let mut expressions = 0;
+
+    for input in inputs.iter() {
+        let result = match input {
+            Input::File(path) => run_file(&mut isolate, path, &dumps),
+            Input::Expression(code) => {
+                expressions += 1;
+                let name = format!("-e #{}", expressions);
+                run_expression(&mut isolate, &name, code, &dumps)
+            }
+            Input::Stdin => {
+                let mut code = String::new();
+                match io::stdin().read_to_string(&mut code) {
+                    Ok(_) => run_expression(&mut isolate, "stdin", &code, &dumps),
+                    Err(error) => Err(isolate::RuntimeError::Io {
+                        path: String::from("stdin"),
+                        message: error.to_string(),
+                    }),
+                }
             }
+        };
 
-            let mut isolate = isolate::Isolate::new();
+        if let Err(error) = result {
+            report_runtime_error(error, &isolate.sources);
+            process::exit(1);
+        }
+    }
+
+    if let Some(path) = &save_snapshot {
+        let result = snapshot::save(&isolate).and_then(|bytes| {
+            fs::write(path, bytes).map_err(|error| isolate::RuntimeError::Io {
+                path: path.display().to_string(),
+                message: error.to_string(),
+            })
+        });
 
-            ast.evaluate(&mut isolate).unwrap();
+        if let Err(error) = result {
+            report_runtime_error(error, &isolate.sources);
+            process::exit(1);
         }
-        Err(e) => match e {
-            parser::ParserError::SyntaticError { location, message } => {
-                let offset = location.offset.try_into().unwrap();
+    }
+}
+
+fn run_file(
+    isolate: &mut isolate::Isolate,
+    path: &Path,
+    dumps: &[Dump],
+) -> Result<(), isolate::RuntimeError> {
+    let code = library::read_file(path)?;
+    let program = parse(isolate, &path.display().to_string(), &code, dumps)?;
+
+    let global = isolate.global();
+    let result = library::evaluate_file(isolate, path, &program, global);
+    dump_expanded(&program, dumps);
+    result?;
+    Ok(())
+}
+
+fn run_expression(
+    isolate: &mut isolate::Isolate,
+    name: &str,
+    code: &str,
+    dumps: &[Dump],
+) -> Result<(), isolate::RuntimeError> {
+    let program = parse(isolate, name, code, dumps)?;
+
+    let result = program.evaluate(isolate);
+    dump_expanded(&program, dumps);
+    result?;
+    Ok(())
+}
+
+/// Parses `code`, showing the tokens and the AST if asked to. Both are shown before the program
+/// runs, so that they are there even when it fails.
+fn parse(
+    isolate: &mut isolate::Isolate,
+    name: &str,
+    code: &str,
+    dumps: &[Dump],
+) -> Result<Program, isolate::RuntimeError> {
+    if dumps.contains(&Dump::Tokens) {
+        dump_tokens(code);
+    }
+
+    let program = library::parse(isolate, name, code)?;
+
+    if dumps.contains(&Dump::Ast) {
+        show(&Printer::new().print(&program));
+    }
+
+    Ok(program)
+}
 
-                Report::build(ReportKind::Error, "stdin", offset)
-                    .with_message("SyntaticError")
-                    .with_label(
-                        Label::new(("stdin", offset..offset + 1)).with_message(message.as_str()),
-                    )
-                    .finish()
-                    .print(sources(vec![("stdin", parser.code)]))
-                    .unwrap();
+/// Prints a token per line. Lexical errors are listed where they occur and lexing goes on
+/// after them; parsing then reports them in full.
+fn dump_tokens(code: &str) {
+    let mut lexer = Lexer::new(code);
+    let mut token = lexer.init();
+    let mut tokens = String::new();
+
+    loop {
+        match token {
+            Ok(current) => {
+                let location = format!("{}:{}", current.row, current.col);
+                tokens.push_str(&format!("{:<8}{:?}\n", location, current.tag));
+
+                if let TokenTag::EOF = current.tag {
+                    break;
+                }
+            }
+            Err(error) => {
+                let location = format!("{}:{}", error.row, error.col);
+                tokens.push_str(&format!("{:<8}error: {}\n", location, error.message));
             }
-            parser::ParserError::LexicalError(lexical_error) => {
-                let offset = lexical_error.offset as usize;
+        }
+        token = lexer.next();
+    }
+
+    show(&tokens);
+}
+
+fn dump_expanded(program: &Program, dumps: &[Dump]) {
+    if dumps.contains(&Dump::Expanded) {
+        show(&Printer::new().with_expansions().print(program));
+    }
+}
+
+/// Writes a dump to stdout. Errors are ignored, e.g. when the output is piped into `head`.
+fn show(text: &str) {
+    let _ = io::stdout().write_all(text.as_bytes());
+}
+
+/// The value of a flag like `--fuel=1000`, if given. Exits if it isn't a number.
+fn numeric_flag<T: std::str::FromStr>(args: &[String], prefix: &str) -> Option<T> {
+    args.iter().find_map(|arg| {
+        let value = arg.strip_prefix(prefix)?;
+        Some(value.parse().unwrap_or_else(|_| {
+            usage_error(&format!(
+                "invalid value {:?} for {}: expected a non-negative integer.",
+                value,
+                prefix.trim_end_matches('=')
+            ))
+        }))
+    })
+}
+
+/// Reports a bad invocation, which isn't the program's fault, and exits with code 2.
+fn usage_error(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(2);
+}
+
+/// Every source as an ariadne cache, keyed by name.
+fn cache(sources: &Sources) -> impl ariadne::Cache<String> + '_ {
+    ariadne::sources(
+        sources
+            .iter()
+            .map(|source| (source.name.clone(), source.code.to_string())),
+    )
+}
+
+fn source_name(sources: &Sources, source: SourceId) -> String {
+    sources
+        .get(source)
+        .map_or_else(|| String::from("<unknown>"), |source| source.name.clone())
+}
+
+fn report_parser_error(e: parser::ParserError, name: String, sources: &Sources) {
+    match e {
+        parser::ParserError::SyntaticError { location, message } => {
+            let span = location.span();
+
+            Report::build(ReportKind::Error, name.clone(), span.start)
+                .with_message("SyntaticError")
+                .with_label(Label::new((name, span)).with_message(message.as_str()))
+                .finish()
+                .print(cache(sources))
+                .unwrap();
+        }
+        parser::ParserError::LexicalError(lexical_error) => {
+            let offset = lexical_error.offset as usize;
+            let end_offset = (lexical_error.end_offset as usize).max(offset + 1);
 
-                Report::build(ReportKind::Error, "stdin", offset)
-                    .with_message("LexicalError")
-                    .with_label(
-                        Label::new(("stdin", offset..offset + 1))
-                            .with_message(lexical_error.message.as_str()),
-                    )
-                    .finish()
-                    .print(sources(vec![("stdin", parser.code)]))
-                    .unwrap();
+            Report::build(ReportKind::Error, name.clone(), offset)
+                .with_message("LexicalError")
+                .with_label(
+                    Label::new((name, offset..end_offset))
+                        .with_message(lexical_error.message.as_str()),
+                )
+                .finish()
+                .print(cache(sources))
+                .unwrap();
+        }
+    }
+}
+
+fn report_runtime_error(error: isolate::RuntimeError, sources: &Sources) {
+    if let isolate::RuntimeError::Unparsable { .. } = error.cause() {
+        if let isolate::RuntimeError::Unparsable { source, errors } = error.untraced() {
+            for e in errors {
+                report_parser_error(e, source_name(sources, source), sources);
             }
-        },
+        }
+        return;
     }
+
+    // Innermost frame first, like the numbering of the labels. Frames in code that comes from
+    // no source, e.g. code built by `eval`, can't be labelled.
+    let frames: Vec<_> = error.frames().iter().rev().collect();
+    let labelled: Vec<_> = frames
+        .iter()
+        .enumerate()
+        .take(MAX_REPORTED_FRAMES)
+        .filter(|(_, frame)| sources.get(frame.location.source).is_some())
+        .collect();
+
+    let (name, offset) = match labelled.first() {
+        Some((_, frame)) => (
+            source_name(sources, frame.location.source),
+            frame.location.span().start,
+        ),
+        None => (
+            sources
+                .iter()
+                .next()
+                .map_or_else(String::new, |source| source.name.clone()),
+            0,
+        ),
+    };
+
+    let mut report = Report::build(ReportKind::Error, name, offset)
+        .with_message(format!("RuntimeError: {}", error));
+
+    for (depth, frame) in labelled.iter() {
+        let message = if *depth == 0 {
+            format!("#{} {}: {}", depth, frame.name, error)
+        } else {
+            format!("#{} {}", depth, frame.name)
+        };
+
+        report = report.with_label(
+            Label::new((
+                source_name(sources, frame.location.source),
+                frame.location.span(),
+            ))
+            .with_message(message)
+            .with_order(*depth as i32),
+        );
+    }
+
+    if !frames.is_empty() {
+        let mut trace: Vec<_> = frames
+            .iter()
+            .enumerate()
+            .take(MAX_REPORTED_FRAMES)
+            .map(|(depth, frame)| {
+                format!(
+                    "#{} {} at {}:{}:{}",
+                    depth,
+                    frame.name,
+                    source_name(sources, frame.location.source),
+                    frame.location.row,
+                    frame.location.col
+                )
+            })
+            .collect();
+
+        if frames.len() > MAX_REPORTED_FRAMES {
+            trace.push(format!(
+                "… {} more frames",
+                frames.len() - MAX_REPORTED_FRAMES
+            ));
+        }
+
+        report = report.with_note(trace.join("\n"));
+    }
+
+    report.finish().print(cache(sources)).unwrap();
 }
@@ -0,0 +1,171 @@
+use crate::isolate::RuntimeError;
+use std::cell::RefCell;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::rc::Rc;
+
+/// Where input comes from once the chars at hand have been consumed.
+#[derive(Debug)]
+pub enum InputSource {
+    Stdin,
+    File {
+        reader: BufReader<File>,
+        path: String,
+    },
+    /// Nothing more: a string port has every char at hand from the start.
+    None,
+}
+
+/// A port to read chars from, e.g. with `read-char` or `read`.
+#[derive(Debug)]
+pub struct InputPort {
+    /// Chars that have been read from the source but not yet consumed.
+    pub buffer: String,
+    pub source: InputSource,
+}
+
+impl InputPort {
+    pub fn stdin() -> Rc<RefCell<InputPort>> {
+        Rc::new(RefCell::new(InputPort {
+            buffer: String::new(),
+            source: InputSource::Stdin,
+        }))
+    }
+
+    pub fn file(file: File, path: &str) -> Rc<RefCell<InputPort>> {
+        Rc::new(RefCell::new(InputPort {
+            buffer: String::new(),
+            source: InputSource::File {
+                reader: BufReader::new(file),
+                path: String::from(path),
+            },
+        }))
+    }
+
+    pub fn string(text: &str) -> Rc<RefCell<InputPort>> {
+        Rc::new(RefCell::new(InputPort {
+            buffer: String::from(text),
+            source: InputSource::None,
+        }))
+    }
+
+    /// Reads another line from the source into the buffer. Returns `false` at the end of the
+    /// input.
+    pub fn fill(&mut self) -> io::Result<bool> {
+        match &mut self.source {
+            InputSource::Stdin => Ok(io::stdin().read_line(&mut self.buffer)? > 0),
+            InputSource::File { reader, .. } => Ok(reader.read_line(&mut self.buffer)? > 0),
+            InputSource::None => Ok(false),
+        }
+    }
+
+    /// `fill`, with a failure to read, e.g. text that isn't UTF-8, turned into an error.
+    pub fn try_fill(&mut self) -> Result<bool, RuntimeError> {
+        self.fill().map_err(|error| RuntimeError::Io {
+            path: match &self.source {
+                InputSource::Stdin => String::from("stdin"),
+                InputSource::File { path, .. } => path.clone(),
+                InputSource::None => String::from("string"),
+            },
+            message: error.to_string(),
+        })
+    }
+
+    /// Drops the first `count` chars of the buffer.
+    pub fn consume(&mut self, count: usize) {
+        self.buffer = self.buffer.chars().skip(count).collect();
+    }
+
+    pub fn read_char(&mut self) -> Result<Option<char>, RuntimeError> {
+        if self.buffer.is_empty() && !self.try_fill()? {
+            return Ok(None);
+        }
+
+        let c = match self.buffer.chars().next() {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+        self.buffer.drain(..c.len_utf8());
+        Ok(Some(c))
+    }
+
+    /// Reads up to the end of the line, which is consumed but not returned.
+    pub fn read_line(&mut self) -> Result<Option<String>, RuntimeError> {
+        while !self.buffer.contains('\n') {
+            if !self.try_fill()? {
+                break;
+            }
+        }
+
+        if self.buffer.is_empty() {
+            return Ok(None);
+        }
+
+        let line = match self.buffer.find('\n') {
+            Some(end) => {
+                let line = String::from(&self.buffer[..end]);
+                self.buffer.drain(..=end);
+                line
+            }
+            None => std::mem::take(&mut self.buffer),
+        };
+
+        Ok(Some(
+            line.strip_suffix('\r').map(String::from).unwrap_or(line),
+        ))
+    }
+}
+
+/// A port to write to, e.g. with `display`.
+#[derive(Debug)]
+pub enum OutputPort {
+    Stdout,
+    /// Collects what is written, for `get-output-string` or an embedder capturing output.
+    String(String),
+    File {
+        file: File,
+        path: String,
+    },
+}
+
+impl OutputPort {
+    pub fn stdout() -> Rc<RefCell<OutputPort>> {
+        Rc::new(RefCell::new(OutputPort::Stdout))
+    }
+
+    pub fn string() -> Rc<RefCell<OutputPort>> {
+        Rc::new(RefCell::new(OutputPort::String(String::new())))
+    }
+
+    pub fn write(&mut self, text: &str) -> Result<(), RuntimeError> {
+        match self {
+            OutputPort::Stdout => {
+                let mut stdout = io::stdout();
+                // Flushed right away, so that nothing is lost if the process exits. Errors are
+                // ignored, so that a closed stdout doesn't stop the program.
+                let _ = stdout
+                    .write_all(text.as_bytes())
+                    .and_then(|_| stdout.flush());
+            }
+            OutputPort::String(buffer) => buffer.push_str(text),
+            OutputPort::File { file, path } => {
+                file.write_all(text.as_bytes())
+                    .map_err(|error| RuntimeError::Io {
+                        path: path.clone(),
+                        message: error.to_string(),
+                    })?
+            }
+        }
+
+        Ok(())
+    }
+
+    /// What has been written so far, for string ports.
+    pub fn contents(&self) -> Option<&str> {
+        match self {
+            OutputPort::Stdout | OutputPort::File { .. } => None,
+            OutputPort::String(buffer) => Some(buffer),
+        }
+    }
+}
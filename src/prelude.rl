@@ -0,0 +1,55 @@
+(define (not x) (if x #f #t))
+
+(define (caar x) (car (car x)))
+(define (cadr x) (car (cdr x)))
+(define (cdar x) (cdr (car x)))
+(define (cddr x) (cdr (cdr x)))
+(define (caddr x) (car (cddr x)))
+
+(define (remove pred list)
+  (filter (lambda (x) (not (pred x))) list))
+
+(define (delete x list)
+  (remove (lambda (y) (equal? x y)) list))
+
+(defmacro let (bindings . body)
+  `((lambda ,(map car bindings) ,@body) ,@(map cadr bindings)))
+
+(defmacro let* (bindings . body)
+  (if (null? bindings)
+      `(let () ,@body)
+      `(let (,(car bindings)) (let* ,(cdr bindings) ,@body))))
+
+(defmacro when (test . body)
+  `(if ,test ((lambda () ,@body))))
+
+(defmacro unless (test . body)
+  `(if ,test (if #f #f) ((lambda () ,@body))))
+
+(defmacro and tests
+  (if (null? tests)
+      #t
+      (if (null? (cdr tests))
+          (car tests)
+          `(if ,(car tests) (and ,@(cdr tests)) #f))))
+
+(defmacro or tests
+  (if (null? tests)
+      #f
+      (if (null? (cdr tests))
+          (car tests)
+          `((lambda (value rest) (if value value (rest)))
+            ,(car tests)
+            (lambda () (or ,@(cdr tests)))))))
+
+(defmacro cond clauses
+  (if (null? clauses)
+      '(if #f #f)
+      (let ((test (caar clauses))
+            (body (cdar clauses))
+            (rest (cdr clauses)))
+        (if (eq? test 'else)
+            `((lambda () ,@body))
+            (if (null? body)
+                `(or ,test (cond ,@rest))
+                `(if ,test ((lambda () ,@body)) (cond ,@rest)))))))
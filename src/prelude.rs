@@ -0,0 +1,18 @@
+use crate::evaluate::Evaluatable;
+use crate::isolate::{Isolate, RuntimeError};
+use crate::library;
+
+/// Library procedures and macros written in rlisp itself, such as `let`, `cond` and `cadr`.
+pub const PRELUDE: &str = include_str!("prelude.rl");
+
+/// Evaluates the prelude in the root environment of `isolate`, next to the natives, so that
+/// programs and the libraries they require see it alike, and can shadow it.
+pub fn install(isolate: &mut Isolate) -> Result<(), RuntimeError> {
+    let program = library::parse(isolate, "prelude", PRELUDE)?;
+
+    isolate.namespaces.push(isolate.root.clone());
+    let result = program.evaluate(isolate);
+    isolate.namespaces.pop();
+
+    result.map(|_| ())
+}
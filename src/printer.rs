@@ -0,0 +1,408 @@
+use crate::ast::*;
+use crate::reader::{Datum, DatumTag};
+use crate::value::Value;
+use std::rc::Rc;
+
+/// Lists longer than this are broken over several lines.
+const DEFAULT_WIDTH: usize = 72;
+
+/// Forms that keep their first operand (a name, parameters or a key) on the line they start.
+const KEEP_FIRST_OPERAND: &[&str] = &["define", "defmacro", "lambda", "if", "guard", "case"];
+
+/// Code laid out as S-expressions, before it is broken into lines.
+#[derive(Debug, Clone)]
+pub enum Doc {
+    Atom {
+        location: Option<Location>,
+        text: String,
+    },
+    List {
+        location: Option<Location>,
+        /// `(`, or `#(` for vectors.
+        open: &'static str,
+        items: Vec<Doc>,
+    },
+    /// Top-level forms, each starting on its own line.
+    Sequence(Vec<Doc>),
+}
+
+impl Doc {
+    pub fn atom(location: &Location, text: impl ToString) -> Doc {
+        Doc::Atom {
+            location: Some(location.clone()),
+            text: text.to_string(),
+        }
+    }
+
+    /// Text that comes from no particular place, e.g. the `else` of a clause.
+    pub fn text(text: impl ToString) -> Doc {
+        Doc::Atom {
+            location: None,
+            text: text.to_string(),
+        }
+    }
+
+    /// A list with no location of its own, e.g. the parameters of a lambda.
+    pub fn group(items: Vec<Doc>) -> Doc {
+        Doc::List {
+            location: None,
+            open: "(",
+            items,
+        }
+    }
+
+    pub fn list(location: &Location, items: Vec<Doc>) -> Doc {
+        Doc::List {
+            location: Some(location.clone()),
+            open: "(",
+            items,
+        }
+    }
+
+    fn keyword(location: &Location, keyword: &str, mut items: Vec<Doc>) -> Doc {
+        items.insert(0, Doc::atom(location, keyword));
+        Doc::list(location, items)
+    }
+
+    fn location(&self) -> Option<&Location> {
+        match self {
+            Doc::Atom { location, .. } | Doc::List { location, .. } => location.as_ref(),
+            Doc::Sequence(_) => None,
+        }
+    }
+
+    /// The doc on a single line.
+    fn flat(&self) -> String {
+        match self {
+            Doc::Atom { text, .. } => text.clone(),
+            Doc::List { open, items, .. } => {
+                let items: Vec<String> = items.iter().map(Doc::flat).collect();
+                format!("{}{})", open, items.join(" "))
+            }
+            Doc::Sequence(items) => {
+                let items: Vec<String> = items.iter().map(Doc::flat).collect();
+                items.join("\n")
+            }
+        }
+    }
+}
+
+/// Turns expressions back into code people can read, as `--dump=ast` shows them.
+pub trait Print {
+    fn doc(&self, printer: &Printer) -> Doc;
+}
+
+/// Lays out the AST as formatted S-expressions. Each line starts with the row and column where
+/// the first expression on it comes from.
+pub struct Printer {
+    /// Whether macro uses that have been evaluated are shown as their expansions.
+    pub expand: bool,
+    pub width: usize,
+}
+
+struct Line {
+    location: Option<Location>,
+    text: String,
+}
+
+impl Printer {
+    pub fn new() -> Printer {
+        Printer {
+            expand: false,
+            width: DEFAULT_WIDTH,
+        }
+    }
+
+    pub fn with_expansions(self) -> Printer {
+        Printer {
+            expand: true,
+            ..self
+        }
+    }
+
+    pub fn print(&self, node: &dyn Print) -> String {
+        let mut lines = vec![];
+        self.layout(&node.doc(self), 0, &mut lines);
+
+        // A location is only shown where it changes: expansions all come from the macro use.
+        let mut previous = None;
+        let gutter: Vec<String> = lines
+            .iter()
+            .map(|line| match &line.location {
+                Some(location) if previous != Some((location.row, location.col)) => {
+                    previous = Some((location.row, location.col));
+                    format!("{}:{}", location.row, location.col)
+                }
+                _ => String::new(),
+            })
+            .collect();
+        let gutter_width = gutter.iter().map(String::len).max().unwrap_or(0);
+
+        let mut output = String::new();
+        for (location, line) in gutter.iter().zip(lines.iter()) {
+            let line = format!("{:<width$}  {}", location, line.text, width = gutter_width);
+            output.push_str(line.trim_end());
+            output.push('\n');
+        }
+        output
+    }
+
+    /// Adds `doc` to `lines`, starting a new line indented by `indent`.
+    fn layout(&self, doc: &Doc, indent: usize, lines: &mut Vec<Line>) {
+        let flat = doc.flat();
+
+        let (open, items) = match doc {
+            Doc::List { open, items, .. } if indent + flat.len() > self.width => (open, items),
+            Doc::Sequence(docs) => {
+                for doc in docs.iter() {
+                    self.layout(doc, indent, lines);
+                }
+                return;
+            }
+            _ => {
+                lines.push(Line {
+                    location: doc.location().cloned(),
+                    text: format!("{:indent$}{}", "", flat, indent = indent),
+                });
+                return;
+            }
+        };
+
+        let mut text = format!("{:indent$}{}", "", open, indent = indent);
+        let mut rest = items.iter().peekable();
+
+        if let Some(Doc::Atom { text: head, .. }) = rest.peek() {
+            text.push_str(head);
+            let keeps_operand = KEEP_FIRST_OPERAND.contains(&head.as_str());
+            rest.next();
+
+            if let (true, Some(operand)) = (keeps_operand, rest.peek()) {
+                let operand = operand.flat();
+                if text.len() + operand.len() < self.width {
+                    text.push(' ');
+                    text.push_str(&operand);
+                    rest.next();
+                }
+            }
+        }
+
+        lines.push(Line {
+            location: doc.location().cloned(),
+            text,
+        });
+
+        for item in rest {
+            self.layout(item, indent + open.len() + 1, lines);
+        }
+
+        // The closing parenthesis goes on the last line, after any nested ones.
+        if let Some(last) = lines.last_mut() {
+            last.text.push(')');
+        }
+    }
+}
+
+impl Default for Printer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn docs(exprs: &[Rc<dyn Expr>], printer: &Printer) -> Vec<Doc> {
+    exprs.iter().map(|expr| expr.doc(printer)).collect()
+}
+
+fn datum_doc(datum: &Datum) -> Doc {
+    let location = Some(datum.location.clone());
+
+    match &datum.tag {
+        DatumTag::List(items) => Doc::List {
+            location,
+            open: "(",
+            items: items.iter().map(datum_doc).collect(),
+        },
+        DatumTag::DottedList(items, tail) => {
+            let mut items: Vec<Doc> = items.iter().map(datum_doc).collect();
+            items.push(Doc::atom(&datum.location, "."));
+            items.push(datum_doc(tail));
+            Doc::List {
+                location,
+                open: "(",
+                items,
+            }
+        }
+        DatumTag::Vector(items) => Doc::List {
+            location,
+            open: "#(",
+            items: items.iter().map(datum_doc).collect(),
+        },
+        _ => Doc::atom(&datum.location, datum.to_value().written()),
+    }
+}
+
+/// `(a b . rest)`, or just `rest` when there are no other parameters.
+fn parameters_doc(lambda: &LambdaExpr, printer: &Printer) -> Doc {
+    let mut parameters: Vec<Doc> = lambda
+        .parameters
+        .iter()
+        .map(|parameter| parameter.doc(printer))
+        .collect();
+
+    match &lambda.rest {
+        Some(rest) if parameters.is_empty() => rest.doc(printer),
+        Some(rest) => {
+            parameters.push(Doc::atom(&rest.location, "."));
+            parameters.push(rest.doc(printer));
+            Doc::group(parameters)
+        }
+        None => Doc::group(parameters),
+    }
+}
+
+impl Print for DefineExpr {
+    fn doc(&self, printer: &Printer) -> Doc {
+        Doc::keyword(
+            &self.location,
+            "define",
+            vec![self.identifier.doc(printer), self.value.doc(printer)],
+        )
+    }
+}
+
+impl Print for CallExpr {
+    fn doc(&self, printer: &Printer) -> Doc {
+        let mut items = vec![self.function.doc(printer)];
+        items.extend(docs(&self.parameters, printer));
+        Doc::list(&self.location, items)
+    }
+}
+
+impl Print for IdentifierExpr {
+    fn doc(&self, _: &Printer) -> Doc {
+        Doc::atom(&self.location, &self.identifer)
+    }
+}
+
+impl Print for IntegerLiteral {
+    fn doc(&self, _: &Printer) -> Doc {
+        Doc::atom(&self.location, self.value)
+    }
+}
+
+impl Print for StringLiteral {
+    fn doc(&self, _: &Printer) -> Doc {
+        Doc::atom(&self.location, Value::String(self.value.clone()).written())
+    }
+}
+
+impl Print for Program {
+    fn doc(&self, printer: &Printer) -> Doc {
+        Doc::Sequence(docs(&self.exprs, printer))
+    }
+}
+
+impl Print for QuoteExpr {
+    fn doc(&self, _: &Printer) -> Doc {
+        match &self.value {
+            // Quasiquotes call natives directly, which are shown by name.
+            Value::NativeThunk(native_thunk) => Doc::atom(&self.location, native_thunk.name),
+            value => Doc::atom(&self.location, format!("'{}", value.written())),
+        }
+    }
+}
+
+impl Print for LambdaExpr {
+    fn doc(&self, printer: &Printer) -> Doc {
+        let mut items = vec![parameters_doc(self, printer)];
+        items.extend(docs(&self.body, printer));
+        Doc::keyword(&self.location, "lambda", items)
+    }
+}
+
+impl Print for IfExpr {
+    fn doc(&self, printer: &Printer) -> Doc {
+        let mut items = vec![self.condition.doc(printer), self.consequent.doc(printer)];
+        if let Some(alternative) = &self.alternative {
+            items.push(alternative.doc(printer));
+        }
+        Doc::keyword(&self.location, "if", items)
+    }
+}
+
+impl Print for DefmacroExpr {
+    fn doc(&self, printer: &Printer) -> Doc {
+        let mut items = vec![
+            self.identifier.doc(printer),
+            parameters_doc(&self.transformer, printer),
+        ];
+        items.extend(docs(&self.transformer.body, printer));
+        Doc::keyword(&self.location, "defmacro", items)
+    }
+}
+
+impl Print for MacroCallExpr {
+    fn doc(&self, printer: &Printer) -> Doc {
+        if printer.expand {
+            if let Some(expansion) = self.expansion.borrow().as_ref() {
+                return expansion.doc(printer);
+            }
+        }
+
+        let mut items = vec![self.identifier.doc(printer)];
+        items.extend(self.operands.iter().map(datum_doc));
+        Doc::list(&self.location, items)
+    }
+}
+
+impl Print for GuardExpr {
+    fn doc(&self, printer: &Printer) -> Doc {
+        let mut specification = vec![self.variable.doc(printer)];
+
+        for clause in self.clauses.iter() {
+            let test = match &clause.test {
+                Some(test) => test.doc(printer),
+                None => Doc::text("else"),
+            };
+            let mut items = vec![test];
+            items.extend(docs(&clause.body, printer));
+            specification.push(Doc::group(items));
+        }
+
+        let mut items = vec![Doc::group(specification)];
+        items.extend(docs(&self.body, printer));
+        Doc::keyword(&self.location, "guard", items)
+    }
+}
+
+impl Print for ExportExpr {
+    fn doc(&self, printer: &Printer) -> Doc {
+        let identifiers = self
+            .identifiers
+            .iter()
+            .map(|identifier| identifier.doc(printer))
+            .collect();
+        Doc::keyword(&self.location, "export", identifiers)
+    }
+}
+
+impl Print for CaseExpr {
+    fn doc(&self, printer: &Printer) -> Doc {
+        let mut items = vec![self.key.doc(printer)];
+
+        for clause in self.clauses.iter() {
+            let data = match &clause.data {
+                Some(data) => Doc::group(
+                    data.iter()
+                        .map(|datum| Doc::text(datum.written()))
+                        .collect(),
+                ),
+                None => Doc::text("else"),
+            };
+            let mut clause_items = vec![data];
+            clause_items.extend(docs(&clause.body, printer));
+            items.push(Doc::group(clause_items));
+        }
+
+        Doc::keyword(&self.location, "case", items)
+    }
+}
@@ -0,0 +1,423 @@
+use crate::ast::Location;
+use crate::lexer;
+use crate::lexer::{Token, TokenTag};
+use crate::parser::ParserError;
+use crate::source::SourceId;
+use crate::value::Value;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+/// Data nested deeper than this aren't read, so that code can't overflow the stack, here or
+/// when it is analyzed and evaluated.
+const MAX_DEPTH: usize = 512;
+
+#[derive(Debug, Clone)]
+pub enum DatumTag {
+    Symbol(String),
+    IntegerLiteral(i32),
+    StringLiteral(String),
+    BooleanLiteral(bool),
+    List(Vec<Datum>),
+    DottedList(Vec<Datum>, Box<Datum>),
+    Vector(Vec<Datum>),
+}
+
+/// Source code read as data, before it is analyzed into expressions.
+#[derive(Debug, Clone)]
+pub struct Datum {
+    pub tag: DatumTag,
+    pub location: Location,
+}
+
+impl Datum {
+    pub fn to_value(&self) -> Value {
+        match &self.tag {
+            DatumTag::Symbol(name) => Value::Symbol(name.clone()),
+            DatumTag::IntegerLiteral(value) => Value::Integer(*value),
+            DatumTag::StringLiteral(value) => Value::String(value.clone()),
+            DatumTag::BooleanLiteral(value) => Value::Boolean(*value),
+            DatumTag::List(items) => Value::list(items.iter().map(Datum::to_value).collect()),
+            DatumTag::DottedList(items, tail) => {
+                Value::list_with_tail(items.iter().map(Datum::to_value).collect(), tail.to_value())
+            }
+            DatumTag::Vector(items) => Value::Vector(Rc::new(RefCell::new(
+                items.iter().map(Datum::to_value).collect(),
+            ))),
+        }
+    }
+
+    /// Turns a value produced at runtime (e.g. a macro expansion) back into code. Every datum
+    /// gets `location`, since values don't remember where they came from.
+    pub fn from_value(value: &Value, location: &Location) -> Result<Datum, String> {
+        Datum::from_value_in(value, location, &mut vec![])
+    }
+
+    /// `converting` holds the vectors being converted, since a vector may contain itself.
+    fn from_value_in(
+        value: &Value,
+        location: &Location,
+        converting: &mut Vec<*const RefCell<Vec<Value>>>,
+    ) -> Result<Datum, String> {
+        let tag = match value {
+            Value::Symbol(name) => DatumTag::Symbol(name.clone()),
+            Value::Integer(value) => DatumTag::IntegerLiteral(*value),
+            Value::String(value) => DatumTag::StringLiteral(value.clone()),
+            Value::Boolean(value) => DatumTag::BooleanLiteral(*value),
+            Value::Nil => DatumTag::List(vec![]),
+            Value::Pair(_) => {
+                let mut items = vec![];
+                let mut cur = value;
+
+                loop {
+                    match cur {
+                        Value::Pair(pair) => {
+                            items.push(Datum::from_value_in(&pair.car, location, converting)?);
+                            cur = &pair.cdr;
+                        }
+                        Value::Nil => break DatumTag::List(items),
+                        tail => {
+                            break DatumTag::DottedList(
+                                items,
+                                Box::new(Datum::from_value_in(tail, location, converting)?),
+                            )
+                        }
+                    }
+                }
+            }
+            Value::Vector(items) if converting.contains(&Rc::as_ptr(items)) => {
+                return Err(String::from(
+                    "a vector containing itself cannot be used as code",
+                ))
+            }
+            Value::Vector(items) => {
+                converting.push(Rc::as_ptr(items));
+                let data = items
+                    .borrow()
+                    .iter()
+                    .map(|item| Datum::from_value_in(item, location, converting))
+                    .collect::<Result<_, _>>();
+                converting.pop();
+                DatumTag::Vector(data?)
+            }
+            _ => return Err(format!("{:?} cannot be used as code", value)),
+        };
+
+        Ok(Datum {
+            tag,
+            location: location.clone(),
+        })
+    }
+
+    pub fn symbol(&self) -> Option<&str> {
+        match &self.tag {
+            DatumTag::Symbol(name) => Some(name.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Returns `x` if this datum is the list `(keyword x)`.
+    pub fn special_operand(&self, keyword: &str) -> Option<&Datum> {
+        match &self.tag {
+            DatumTag::List(items) if items.len() == 2 && items[0].symbol() == Some(keyword) => {
+                Some(&items[1])
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Datum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.tag {
+            DatumTag::Symbol(name) => write!(f, "{}", name),
+            DatumTag::IntegerLiteral(value) => write!(f, "{}", value),
+            DatumTag::StringLiteral(value) => write!(f, "{:?}", value),
+            DatumTag::BooleanLiteral(value) => write!(f, "{}", if *value { "#t" } else { "#f" }),
+            DatumTag::List(items) | DatumTag::Vector(items) => {
+                if let DatumTag::Vector(_) = self.tag {
+                    write!(f, "#")?;
+                }
+                write!(f, "(")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, ")")
+            }
+            DatumTag::DottedList(items, tail) => {
+                write!(f, "(")?;
+                for item in items.iter() {
+                    write!(f, "{} ", item)?;
+                }
+                write!(f, ". {})", tail)
+            }
+        }
+    }
+}
+
+/// Reads tokens into data. `read` can be called repeatedly to read a sequence of data.
+pub struct Reader<'a> {
+    lexer: lexer::Lexer<'a>,
+    /// `None` before `init` and after a lexical error.
+    cur_token: Option<lexer::Token>,
+    /// How many lists are open before the current token.
+    depth: usize,
+    /// How many data are being read, each inside the previous one.
+    nesting: usize,
+    /// An error lexing the token after a whole datum, reported when reading on, since that
+    /// token belongs to the next datum.
+    deferred: Option<ParserError>,
+    source: SourceId,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(code: &'a str) -> Reader<'a> {
+        Reader::with_source(code, SourceId::default())
+    }
+
+    /// A reader whose data are located in `source`.
+    pub fn with_source(code: &'a str, source: SourceId) -> Reader<'a> {
+        Reader {
+            lexer: lexer::Lexer::new(code),
+            cur_token: None,
+            depth: 0,
+            nesting: 0,
+            deferred: None,
+            source,
+        }
+    }
+
+    pub fn location(&self, token: &Token) -> Location {
+        Location {
+            source: self.source,
+            col: token.col,
+            row: token.row,
+            offset: token.offset,
+            end_offset: token.end_offset,
+        }
+    }
+
+    pub fn init(&mut self) -> Result<(), lexer::LexicalError> {
+        self.cur_token = Some(self.lexer.init()?);
+        Ok(())
+    }
+
+    pub fn next_token(&mut self) -> Result<Token, ParserError> {
+        match self.cur_token.take().map(|token| token.tag) {
+            Some(TokenTag::LParen | TokenTag::VectorStart) => self.depth += 1,
+            Some(TokenTag::RParen) => self.depth = self.depth.saturating_sub(1),
+            _ => {}
+        }
+        self.cur_token = Some(self.lexer.next()?);
+        Ok(self.cur_token())
+    }
+
+    pub fn cur_token(&self) -> Token {
+        self.cur_token.as_ref().unwrap().clone()
+    }
+
+    /// Skips the rest of the top-level datum in which an error was found, so that reading can
+    /// go on from the next one. Lexical errors met on the way are returned.
+    pub fn recover(&mut self) -> Vec<ParserError> {
+        let mut errors = vec![];
+        // The token at which the error was found is never a good place to restart.
+        let mut skip = self.cur_token.is_some();
+
+        loop {
+            match &self.cur_token {
+                Some(Token {
+                    tag: TokenTag::EOF, ..
+                }) => return errors,
+                Some(_) if self.depth == 0 && !skip => return errors,
+                _ => {}
+            }
+
+            skip = false;
+
+            if let Err(error) = self.next_token() {
+                errors.push(error);
+            }
+        }
+    }
+
+    /// Whether every datum has been read.
+    pub fn is_at_end(&self) -> bool {
+        matches!(
+            self.cur_token,
+            Some(Token {
+                tag: TokenTag::EOF,
+                ..
+            })
+        )
+    }
+
+    /// Whether the lexer has run out of code, e.g. because an error was reported at its end.
+    /// More code might complete the datum in that case.
+    pub fn is_exhausted(&self) -> bool {
+        self.lexer.is_at_end()
+    }
+
+    pub fn read(&mut self) -> Result<Datum, ParserError> {
+        if let Some(error) = self.deferred.take() {
+            return Err(error);
+        }
+
+        if self.nesting >= MAX_DEPTH {
+            return Err(ParserError::SyntaticError {
+                location: self.location(&self.cur_token()),
+                message: String::from("data nested too deeply. "),
+            });
+        }
+
+        self.nesting += 1;
+        let result = self.read_nested();
+        self.nesting -= 1;
+        result
+    }
+
+    /// Moves past the last token of a datum.
+    fn finish(&mut self) -> Result<(), ParserError> {
+        match self.next_token() {
+            Err(error) if self.nesting == 1 => {
+                self.deferred = Some(error);
+                Ok(())
+            }
+            result => result.map(|_| ()),
+        }
+    }
+
+    fn read_nested(&mut self) -> Result<Datum, ParserError> {
+        let first_token = self.cur_token();
+        let location = self.location(&first_token);
+
+        let tag = match first_token.tag {
+            TokenTag::LParen => {
+                // '('
+                return self.read_list();
+            }
+            TokenTag::VectorStart => return self.read_vector(),
+            TokenTag::Quote => return self.read_abbreviation("quote"),
+            TokenTag::Quasiquote => return self.read_abbreviation("quasiquote"),
+            TokenTag::Unquote => return self.read_abbreviation("unquote"),
+            TokenTag::UnquoteSplicing => return self.read_abbreviation("unquote-splicing"),
+            TokenTag::Identifier(identifier) => DatumTag::Symbol(identifier),
+            TokenTag::IntegerLiteral(value) => DatumTag::IntegerLiteral(value),
+            TokenTag::StringLiteral(value) => DatumTag::StringLiteral(value),
+            TokenTag::BooleanLiteral(value) => DatumTag::BooleanLiteral(value),
+            _ => {
+                return Err(ParserError::SyntaticError {
+                    location,
+                    message: String::from("unexpected token when parsing expression. "),
+                })
+            }
+        };
+
+        self.finish()?;
+
+        Ok(Datum { tag, location })
+    }
+
+    fn read_list(&mut self) -> Result<Datum, ParserError> {
+        // read '('
+        let lparen = self.cur_token();
+        let mut items = Vec::<Datum>::new();
+
+        self.next_token()?;
+
+        loop {
+            let token = self.cur_token();
+
+            match token.tag {
+                TokenTag::RParen => {
+                    self.finish()?;
+                    return Ok(Datum {
+                        tag: DatumTag::List(items),
+                        location: self.location(&lparen).to(&self.location(&token)),
+                    });
+                }
+                TokenTag::Dot if !items.is_empty() => {
+                    self.next_token()?;
+                    let tail = self.read()?;
+
+                    let rparen = self.cur_token();
+
+                    if let TokenTag::RParen = rparen.tag {
+                        self.finish()?;
+                    } else {
+                        return Err(ParserError::SyntaticError {
+                            location: self.location(&rparen),
+                            message: String::from(
+                                "expecting ')' after the tail of a dotted list. ",
+                            ),
+                        });
+                    }
+
+                    return Ok(Datum {
+                        tag: DatumTag::DottedList(items, Box::new(tail)),
+                        location: self.location(&lparen).to(&self.location(&rparen)),
+                    });
+                }
+                TokenTag::EOF => {
+                    return Err(ParserError::SyntaticError {
+                        location: self.location(&lparen).to(&self.location(&token)),
+                        message: String::from("expecting ')' to close this list. "),
+                    })
+                }
+                _ => items.push(self.read()?),
+            }
+        }
+    }
+
+    fn read_vector(&mut self) -> Result<Datum, ParserError> {
+        // read '#('
+        let start = self.cur_token();
+        let mut items = Vec::<Datum>::new();
+
+        self.next_token()?;
+
+        loop {
+            let token = self.cur_token();
+
+            match token.tag {
+                TokenTag::RParen => {
+                    self.finish()?;
+                    return Ok(Datum {
+                        tag: DatumTag::Vector(items),
+                        location: self.location(&start).to(&self.location(&token)),
+                    });
+                }
+                TokenTag::EOF => {
+                    return Err(ParserError::SyntaticError {
+                        location: self.location(&start).to(&self.location(&token)),
+                        message: String::from("expecting ')' to close this vector. "),
+                    })
+                }
+                _ => items.push(self.read()?),
+            }
+        }
+    }
+
+    fn read_abbreviation(&mut self, keyword: &str) -> Result<Datum, ParserError> {
+        // read one of '`,@
+        let location = self.location(&self.cur_token());
+
+        self.next_token()?;
+
+        let datum = self.read()?;
+        let datum_location = datum.location.clone();
+
+        Ok(Datum {
+            tag: DatumTag::List(vec![
+                Datum {
+                    tag: DatumTag::Symbol(String::from(keyword)),
+                    location: location.clone(),
+                },
+                datum,
+            ]),
+            location: location.to(&datum_location),
+        })
+    }
+}
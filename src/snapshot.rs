@@ -0,0 +1,1238 @@
+use crate::ast::*;
+use crate::gc::Object;
+use crate::isolate::{Environment, Isolate, Namespace, RuntimeError};
+use crate::reader::{Datum, DatumTag};
+use crate::source::SourceId;
+use crate::value::{Continuation, ErrorObject, Thunk, Value};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Snapshots start with these bytes, followed by `VERSION`.
+const MAGIC: &[u8] = b"rlisp-snapshot";
+const VERSION: u32 = 1;
+
+/// Values, expressions and environments nested deeper than this in a snapshot make it invalid,
+/// rather than overflowing the stack while it is restored. A level can take kilobytes of stack in
+/// debug builds, and this many fit in the 2 MiB a spawned thread gets.
+const MAX_DEPTH: usize = 128;
+
+const VALUE_INTEGER: u8 = 0;
+const VALUE_STRING: u8 = 1;
+const VALUE_BOOLEAN: u8 = 2;
+const VALUE_SYMBOL: u8 = 3;
+const VALUE_LIST: u8 = 4;
+const VALUE_NIL: u8 = 5;
+const VALUE_NONE: u8 = 6;
+const VALUE_EOF: u8 = 7;
+const VALUE_THUNK: u8 = 8;
+const VALUE_NATIVE_THUNK: u8 = 9;
+const VALUE_MACRO: u8 = 10;
+const VALUE_ENVIRONMENT: u8 = 11;
+const VALUE_ERROR: u8 = 12;
+const VALUE_CONTINUATION: u8 = 13;
+const VALUE_VECTOR: u8 = 14;
+const VALUE_HASH_TABLE: u8 = 15;
+
+/// An object already in the snapshot, referred to by its index.
+const OBJECT_REFERENCE: u8 = 0;
+/// An object seen for the first time, followed by its contents.
+const OBJECT_NEW: u8 = 1;
+const OBJECT_ROOT: u8 = 2;
+const OBJECT_GLOBAL: u8 = 3;
+
+const EXPR_DEFINE: u8 = 0;
+const EXPR_CALL: u8 = 1;
+const EXPR_IDENTIFIER: u8 = 2;
+const EXPR_INTEGER: u8 = 3;
+const EXPR_STRING: u8 = 4;
+const EXPR_QUOTE: u8 = 5;
+const EXPR_LAMBDA: u8 = 6;
+const EXPR_IF: u8 = 7;
+const EXPR_DEFMACRO: u8 = 8;
+const EXPR_MACRO_CALL: u8 = 9;
+const EXPR_GUARD: u8 = 10;
+const EXPR_EXPORT: u8 = 11;
+const EXPR_CASE: u8 = 12;
+
+const DATUM_SYMBOL: u8 = 0;
+const DATUM_INTEGER: u8 = 1;
+const DATUM_STRING: u8 = 2;
+const DATUM_BOOLEAN: u8 = 3;
+const DATUM_LIST: u8 = 4;
+const DATUM_DOTTED_LIST: u8 = 5;
+const DATUM_VECTOR: u8 = 6;
+
+/// Saves the definitions of `isolate` so that another isolate can start from them with
+/// `restore`, without parsing and evaluating them again.
+///
+/// The snapshot holds the global environment and what the root environment has besides natives
+/// (e.g. the prelude), along with everything they refer to: closures with their code, macros,
+/// and the sources the code came from, for error reports. Natives are saved by name. Ports can't
+/// be saved, continuations are restored expired, and libraries loaded by `require` are loaded
+/// again when next required. Values nested too deeply for `restore` can't be saved either.
+///
+/// ```
+/// use rlisp::evaluate::Evaluatable;
+/// use rlisp::isolate::{Isolate, IsolateBuilder};
+/// use rlisp::{library, snapshot};
+///
+/// let mut warm = Isolate::new();
+/// let program = library::parse(&mut warm, "setup", "(define (twice x) (list x x))").unwrap();
+/// program.evaluate(&mut warm).unwrap();
+/// let bytes = snapshot::save(&warm).unwrap();
+///
+/// // The prelude comes with the snapshot.
+/// let mut cold = IsolateBuilder::new().without_prelude().build();
+/// snapshot::restore(&mut cold, &bytes).unwrap();
+/// let program = library::parse(&mut cold, "use", "(twice (cadr '(1 2)))").unwrap();
+/// program.evaluate(&mut cold).unwrap();
+/// ```
+pub fn save(isolate: &Isolate) -> Result<Vec<u8>, RuntimeError> {
+    let global = isolate.global();
+    let mut encoder = Encoder {
+        bytes: MAGIC.to_vec(),
+        root: Rc::as_ptr(&isolate.root),
+        global: Rc::as_ptr(&global),
+        objects: HashMap::new(),
+        lambdas: HashMap::new(),
+        depth: 0,
+    };
+
+    encoder.u32(VERSION as usize);
+
+    let sources: Vec<_> = isolate.sources.iter().collect();
+    encoder.u32(sources.len());
+    for source in sources {
+        encoder.string(&source.name);
+        encoder.string(&source.code);
+    }
+
+    let mut macros: Vec<_> = isolate.macros.iter().collect();
+    macros.sort();
+    encoder.u32(macros.len());
+    for name in macros {
+        encoder.string(name);
+    }
+
+    encoder.bindings(&isolate.root.borrow().variables, true)?;
+    encoder.bindings(&global.borrow().variables, false)?;
+
+    Ok(encoder.bytes)
+}
+
+/// Defines what the snapshot saved by `save` holds in `isolate`. The natives it refers to must
+/// be installed in `isolate`.
+pub fn restore(isolate: &mut Isolate, bytes: &[u8]) -> Result<(), RuntimeError> {
+    let mut decoder = Decoder {
+        bytes,
+        position: 0,
+        isolate,
+        sources: vec![],
+        objects: vec![],
+        lambdas: vec![],
+        depth: 0,
+    };
+
+    if !bytes.starts_with(MAGIC) {
+        return Err(invalid("not a snapshot"));
+    }
+    decoder.position = MAGIC.len();
+
+    let version = decoder.u32()?;
+    if version != VERSION as usize {
+        return Err(invalid(&format!("unsupported version {}", version)));
+    }
+
+    for _ in 0..decoder.u32()? {
+        let name = decoder.string()?;
+        let code = decoder.string()?;
+        let source = decoder.isolate.sources.add(&name, &code);
+        decoder.sources.push(source);
+    }
+
+    for _ in 0..decoder.u32()? {
+        let name = decoder.string()?;
+        decoder.isolate.macros.insert(name);
+    }
+
+    let root = decoder.bindings()?;
+    let global = decoder.bindings()?;
+
+    if decoder.position != bytes.len() {
+        return Err(invalid("unexpected bytes at the end"));
+    }
+
+    isolate.root.borrow_mut().variables.extend(root);
+    isolate.global().borrow_mut().variables.extend(global);
+
+    Ok(())
+}
+
+fn invalid(message: &str) -> RuntimeError {
+    RuntimeError::InvalidSnapshot {
+        message: String::from(message),
+    }
+}
+
+/// Writes values and code into a snapshot. Environments, vectors, hash tables and lambda
+/// expressions are written once and referred to by index afterwards, which keeps them shared and
+/// lets them refer to each other.
+pub struct Encoder {
+    bytes: Vec<u8>,
+    root: *const RefCell<Namespace>,
+    global: *const RefCell<Namespace>,
+    objects: HashMap<*const (), usize>,
+    lambdas: HashMap<*const LambdaExpr, usize>,
+    /// How many values, expressions and environments are being written, counted like the
+    /// decoder counts them, so that a snapshot that saves also restores.
+    depth: usize,
+}
+
+impl Encoder {
+    /// Calls `write`, which may write what it contains by calling `nested` again.
+    fn nested(
+        &mut self,
+        write: impl FnOnce(&mut Self) -> Result<(), RuntimeError>,
+    ) -> Result<(), RuntimeError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(RuntimeError::Unserializable {
+                value: format!("values nested more than {} levels deep", MAX_DEPTH),
+                format: String::from("a snapshot"),
+            });
+        }
+
+        self.depth += 1;
+        let result = write(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u32(&mut self, value: usize) {
+        self.bytes.extend((value as u32).to_le_bytes());
+    }
+
+    fn i32(&mut self, value: i32) {
+        self.bytes.extend(value.to_le_bytes());
+    }
+
+    fn string(&mut self, value: &str) {
+        self.u32(value.len());
+        self.bytes.extend(value.as_bytes());
+    }
+
+    fn location(&mut self, location: &Location) {
+        self.u32(location.source.number());
+        self.i32(location.offset);
+        self.i32(location.col);
+        self.i32(location.row);
+        self.i32(location.end_offset);
+    }
+
+    /// Writes a marker for the object at `address`. Returns whether it is new, in which case
+    /// its contents must follow.
+    fn object(&mut self, address: *const ()) -> bool {
+        match self.objects.get(&address) {
+            Some(&index) => {
+                self.u8(OBJECT_REFERENCE);
+                self.u32(index);
+                false
+            }
+            None => {
+                self.objects.insert(address, self.objects.len());
+                self.u8(OBJECT_NEW);
+                true
+            }
+        }
+    }
+
+    /// Writes the variables sorted by name, so that the same definitions give the same snapshot.
+    fn bindings(
+        &mut self,
+        variables: &HashMap<String, Value>,
+        skip_natives: bool,
+    ) -> Result<(), RuntimeError> {
+        let mut names: Vec<_> = variables
+            .iter()
+            .filter(|(name, value)| {
+                !skip_natives
+                    || !matches!(value, Value::NativeThunk(native) if native.name == name.as_str())
+            })
+            .map(|(name, _)| name)
+            .collect();
+        names.sort();
+
+        self.u32(names.len());
+        for name in names {
+            self.string(name);
+            self.value(&variables[name])?;
+        }
+
+        Ok(())
+    }
+
+    fn environment(&mut self, environment: &Environment) -> Result<(), RuntimeError> {
+        self.nested(|encoder| encoder.write_environment(environment))
+    }
+
+    fn write_environment(&mut self, environment: &Environment) -> Result<(), RuntimeError> {
+        let address = Rc::as_ptr(environment);
+
+        if address == self.root {
+            self.u8(OBJECT_ROOT);
+        } else if address == self.global {
+            self.u8(OBJECT_GLOBAL);
+        } else if self.object(address as *const ()) {
+            let namespace = environment.borrow();
+
+            match &namespace.parent {
+                Some(parent) => {
+                    self.u8(1);
+                    self.environment(parent)?;
+                }
+                None => self.u8(0),
+            }
+
+            self.bindings(&namespace.variables, false)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn value(&mut self, value: &Value) -> Result<(), RuntimeError> {
+        self.nested(|encoder| encoder.write_value(value))
+    }
+
+    fn write_value(&mut self, value: &Value) -> Result<(), RuntimeError> {
+        match value {
+            Value::Integer(value) => {
+                self.u8(VALUE_INTEGER);
+                self.i32(*value);
+            }
+            Value::String(value) => {
+                self.u8(VALUE_STRING);
+                self.string(value);
+            }
+            Value::Boolean(value) => {
+                self.u8(VALUE_BOOLEAN);
+                self.u8(*value as u8);
+            }
+            Value::Symbol(name) => {
+                self.u8(VALUE_SYMBOL);
+                self.string(name);
+            }
+            Value::Pair(_) => {
+                // Written as a sequence, so that long lists don't nest deeply.
+                let mut items = vec![];
+                let mut cur = value;
+
+                while let Value::Pair(pair) = cur {
+                    items.push(&pair.car);
+                    cur = &pair.cdr;
+                }
+
+                self.u8(VALUE_LIST);
+                self.u32(items.len());
+                for item in items {
+                    self.value(item)?;
+                }
+                self.value(cur)?;
+            }
+            Value::Nil => self.u8(VALUE_NIL),
+            Value::None => self.u8(VALUE_NONE),
+            Value::Eof => self.u8(VALUE_EOF),
+            Value::Thunk(thunk) | Value::Macro(thunk) => {
+                self.u8(if let Value::Thunk(_) = value {
+                    VALUE_THUNK
+                } else {
+                    VALUE_MACRO
+                });
+                self.lambda(&thunk.source)?;
+                self.environment(&thunk.closure)?;
+            }
+            Value::NativeThunk(native_thunk) => {
+                self.u8(VALUE_NATIVE_THUNK);
+                self.string(native_thunk.name);
+            }
+            Value::Environment(environment) => {
+                self.u8(VALUE_ENVIRONMENT);
+                self.environment(environment)?;
+            }
+            Value::Error(error) => {
+                self.u8(VALUE_ERROR);
+                self.string(&error.kind);
+                self.string(&error.message);
+                self.u32(error.irritants.len());
+                for irritant in error.irritants.iter() {
+                    self.value(irritant)?;
+                }
+            }
+            Value::Continuation(_) => self.u8(VALUE_CONTINUATION),
+            Value::Vector(items) => {
+                self.u8(VALUE_VECTOR);
+                if self.object(Rc::as_ptr(items) as *const ()) {
+                    let items = items.borrow();
+                    self.u32(items.len());
+                    for item in items.iter() {
+                        self.value(item)?;
+                    }
+                }
+            }
+            Value::HashTable(table) => {
+                self.u8(VALUE_HASH_TABLE);
+                if self.object(Rc::as_ptr(table) as *const ()) {
+                    let table = table.borrow();
+                    self.u32(table.len());
+                    for (key, value) in table.iter() {
+                        self.value(key)?;
+                        self.value(value)?;
+                    }
+                }
+            }
+            Value::InputPort(_) | Value::OutputPort(_) => {
+                return Err(RuntimeError::Unserializable {
+                    value: value.to_string(),
+                    format: String::from("a snapshot"),
+                })
+            }
+        }
+
+        Ok(())
+    }
+
+    fn lambda(&mut self, lambda: &LambdaExpr) -> Result<(), RuntimeError> {
+        self.nested(|encoder| encoder.write_lambda(lambda))
+    }
+
+    fn write_lambda(&mut self, lambda: &LambdaExpr) -> Result<(), RuntimeError> {
+        let address = lambda as *const LambdaExpr;
+
+        if let Some(&index) = self.lambdas.get(&address) {
+            self.u8(OBJECT_REFERENCE);
+            self.u32(index);
+            return Ok(());
+        }
+
+        self.lambdas.insert(address, self.lambdas.len());
+        self.u8(OBJECT_NEW);
+        self.location(&lambda.location);
+        self.optional_string(lambda.name.as_deref());
+        self.u32(lambda.parameters.len());
+        for parameter in lambda.parameters.iter() {
+            self.identifier(parameter);
+        }
+        match &lambda.rest {
+            Some(rest) => {
+                self.u8(1);
+                self.identifier(rest);
+            }
+            None => self.u8(0),
+        }
+        self.exprs(&lambda.body)
+    }
+
+    fn optional_string(&mut self, value: Option<&str>) {
+        match value {
+            Some(value) => {
+                self.u8(1);
+                self.string(value);
+            }
+            None => self.u8(0),
+        }
+    }
+
+    fn identifier(&mut self, identifier: &IdentifierExpr) {
+        self.location(&identifier.location);
+        self.string(&identifier.identifer);
+    }
+
+    fn expr(&mut self, expr: &dyn Expr) -> Result<(), RuntimeError> {
+        self.nested(|encoder| expr.encode(encoder))
+    }
+
+    fn optional_expr(&mut self, expr: &Option<Rc<dyn Expr>>) -> Result<(), RuntimeError> {
+        match expr {
+            Some(expr) => {
+                self.u8(1);
+                self.expr(&**expr)
+            }
+            None => {
+                self.u8(0);
+                Ok(())
+            }
+        }
+    }
+
+    fn exprs(&mut self, exprs: &[Rc<dyn Expr>]) -> Result<(), RuntimeError> {
+        self.u32(exprs.len());
+        for expr in exprs.iter() {
+            self.expr(&**expr)?;
+        }
+        Ok(())
+    }
+
+    fn datum(&mut self, datum: &Datum) -> Result<(), RuntimeError> {
+        self.nested(|encoder| encoder.write_datum(datum))
+    }
+
+    fn write_datum(&mut self, datum: &Datum) -> Result<(), RuntimeError> {
+        self.location(&datum.location);
+
+        match &datum.tag {
+            DatumTag::Symbol(name) => {
+                self.u8(DATUM_SYMBOL);
+                self.string(name);
+            }
+            DatumTag::IntegerLiteral(value) => {
+                self.u8(DATUM_INTEGER);
+                self.i32(*value);
+            }
+            DatumTag::StringLiteral(value) => {
+                self.u8(DATUM_STRING);
+                self.string(value);
+            }
+            DatumTag::BooleanLiteral(value) => {
+                self.u8(DATUM_BOOLEAN);
+                self.u8(*value as u8);
+            }
+            DatumTag::List(items) | DatumTag::Vector(items) => {
+                self.u8(if let DatumTag::List(_) = datum.tag {
+                    DATUM_LIST
+                } else {
+                    DATUM_VECTOR
+                });
+                self.data(items)?;
+            }
+            DatumTag::DottedList(items, tail) => {
+                self.u8(DATUM_DOTTED_LIST);
+                self.data(items)?;
+                self.datum(tail)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn data(&mut self, data: &[Datum]) -> Result<(), RuntimeError> {
+        self.u32(data.len());
+        for datum in data.iter() {
+            self.datum(datum)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes an expression into a snapshot, starting with a tag telling what kind of expression
+/// it is.
+pub trait Encode {
+    fn encode(&self, encoder: &mut Encoder) -> Result<(), RuntimeError>;
+}
+
+impl Encode for DefineExpr {
+    fn encode(&self, encoder: &mut Encoder) -> Result<(), RuntimeError> {
+        encoder.u8(EXPR_DEFINE);
+        encoder.location(&self.location);
+        encoder.identifier(&self.identifier);
+        encoder.expr(&*self.value)
+    }
+}
+
+impl Encode for CallExpr {
+    fn encode(&self, encoder: &mut Encoder) -> Result<(), RuntimeError> {
+        encoder.u8(EXPR_CALL);
+        encoder.location(&self.location);
+        encoder.expr(&*self.function)?;
+        encoder.exprs(&self.parameters)
+    }
+}
+
+impl Encode for IdentifierExpr {
+    fn encode(&self, encoder: &mut Encoder) -> Result<(), RuntimeError> {
+        encoder.u8(EXPR_IDENTIFIER);
+        encoder.identifier(self);
+        Ok(())
+    }
+}
+
+impl Encode for IntegerLiteral {
+    fn encode(&self, encoder: &mut Encoder) -> Result<(), RuntimeError> {
+        encoder.u8(EXPR_INTEGER);
+        encoder.location(&self.location);
+        encoder.i32(self.value);
+        Ok(())
+    }
+}
+
+impl Encode for StringLiteral {
+    fn encode(&self, encoder: &mut Encoder) -> Result<(), RuntimeError> {
+        encoder.u8(EXPR_STRING);
+        encoder.location(&self.location);
+        encoder.string(&self.value);
+        Ok(())
+    }
+}
+
+/// Programs aren't part of snapshots, which only hold what programs have defined.
+impl Encode for Program {
+    fn encode(&self, encoder: &mut Encoder) -> Result<(), RuntimeError> {
+        encoder.exprs(&self.exprs)
+    }
+}
+
+impl Encode for QuoteExpr {
+    fn encode(&self, encoder: &mut Encoder) -> Result<(), RuntimeError> {
+        encoder.u8(EXPR_QUOTE);
+        encoder.location(&self.location);
+        encoder.value(&self.value)
+    }
+}
+
+impl Encode for LambdaExpr {
+    fn encode(&self, encoder: &mut Encoder) -> Result<(), RuntimeError> {
+        encoder.u8(EXPR_LAMBDA);
+        encoder.lambda(self)
+    }
+}
+
+impl Encode for IfExpr {
+    fn encode(&self, encoder: &mut Encoder) -> Result<(), RuntimeError> {
+        encoder.u8(EXPR_IF);
+        encoder.location(&self.location);
+        encoder.expr(&*self.condition)?;
+        encoder.expr(&*self.consequent)?;
+        encoder.optional_expr(&self.alternative)
+    }
+}
+
+impl Encode for DefmacroExpr {
+    fn encode(&self, encoder: &mut Encoder) -> Result<(), RuntimeError> {
+        encoder.u8(EXPR_DEFMACRO);
+        encoder.location(&self.location);
+        encoder.identifier(&self.identifier);
+        encoder.lambda(&self.transformer)
+    }
+}
+
+/// The expansion isn't saved: it is made again when the expression is next evaluated.
+impl Encode for MacroCallExpr {
+    fn encode(&self, encoder: &mut Encoder) -> Result<(), RuntimeError> {
+        encoder.u8(EXPR_MACRO_CALL);
+        encoder.location(&self.location);
+        encoder.identifier(&self.identifier);
+        encoder.data(&self.operands)?;
+        Ok(())
+    }
+}
+
+impl Encode for GuardExpr {
+    fn encode(&self, encoder: &mut Encoder) -> Result<(), RuntimeError> {
+        encoder.u8(EXPR_GUARD);
+        encoder.location(&self.location);
+        encoder.identifier(&self.variable);
+        encoder.u32(self.clauses.len());
+        for clause in self.clauses.iter() {
+            encoder.optional_expr(&clause.test)?;
+            encoder.exprs(&clause.body)?;
+        }
+        encoder.exprs(&self.body)
+    }
+}
+
+impl Encode for ExportExpr {
+    fn encode(&self, encoder: &mut Encoder) -> Result<(), RuntimeError> {
+        encoder.u8(EXPR_EXPORT);
+        encoder.location(&self.location);
+        encoder.u32(self.identifiers.len());
+        for identifier in self.identifiers.iter() {
+            encoder.identifier(identifier);
+        }
+        Ok(())
+    }
+}
+
+impl Encode for CaseExpr {
+    fn encode(&self, encoder: &mut Encoder) -> Result<(), RuntimeError> {
+        encoder.u8(EXPR_CASE);
+        encoder.location(&self.location);
+        encoder.expr(&*self.key)?;
+        encoder.u32(self.clauses.len());
+        for clause in self.clauses.iter() {
+            match &clause.data {
+                Some(data) => {
+                    encoder.u8(1);
+                    encoder.u32(data.len());
+                    for datum in data.iter() {
+                        encoder.value(datum)?;
+                    }
+                }
+                None => encoder.u8(0),
+            }
+            encoder.exprs(&clause.body)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads what `Encoder` wrote, creating the objects in the isolate.
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    isolate: &'a mut Isolate,
+    /// The ids the saved sources were given in the isolate, in the order they were saved.
+    sources: Vec<SourceId>,
+    objects: Vec<Object>,
+    /// `None` while the lambda expression is being read.
+    lambdas: Vec<Option<Rc<LambdaExpr>>>,
+    /// How many values, expressions and environments are being read.
+    depth: usize,
+}
+
+impl Decoder<'_> {
+    /// Calls `read`, which may read what it contains by calling `nested` again.
+    fn nested<T>(
+        &mut self,
+        read: impl FnOnce(&mut Self) -> Result<T, RuntimeError>,
+    ) -> Result<T, RuntimeError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(invalid("nested too deeply"));
+        }
+
+        self.depth += 1;
+        let result = read(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn take(&mut self, count: usize) -> Result<&[u8], RuntimeError> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + count)
+            .ok_or_else(|| invalid("unexpected end"))?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, RuntimeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool, RuntimeError> {
+        Ok(self.u8()? != 0)
+    }
+
+    fn u32(&mut self) -> Result<usize, RuntimeError> {
+        let bytes = self.take(4)?.try_into().unwrap();
+        Ok(u32::from_le_bytes(bytes) as usize)
+    }
+
+    fn i32(&mut self) -> Result<i32, RuntimeError> {
+        let bytes = self.take(4)?.try_into().unwrap();
+        Ok(i32::from_le_bytes(bytes))
+    }
+
+    fn string(&mut self) -> Result<String, RuntimeError> {
+        let length = self.u32()?;
+        let bytes = self.take(length)?.to_vec();
+        String::from_utf8(bytes).map_err(|_| invalid("malformed string"))
+    }
+
+    fn optional_string(&mut self) -> Result<Option<String>, RuntimeError> {
+        if self.bool()? {
+            Ok(Some(self.string()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn location(&mut self) -> Result<Location, RuntimeError> {
+        let source = match self.u32()? {
+            0 => SourceId::default(),
+            number => *self
+                .sources
+                .get(number - 1)
+                .ok_or_else(|| invalid("unknown source"))?,
+        };
+
+        Ok(Location {
+            source,
+            offset: self.i32()?,
+            col: self.i32()?,
+            row: self.i32()?,
+            end_offset: self.i32()?,
+        })
+    }
+
+    fn object(&mut self) -> Result<Object, RuntimeError> {
+        let index = self.u32()?;
+        self.objects
+            .get(index)
+            .cloned()
+            .ok_or_else(|| invalid("unknown object"))
+    }
+
+    fn bindings(&mut self) -> Result<HashMap<String, Value>, RuntimeError> {
+        let mut variables = HashMap::new();
+
+        for _ in 0..self.u32()? {
+            let name = self.string()?;
+            let value = self.value()?;
+            variables.insert(name, value);
+        }
+
+        Ok(variables)
+    }
+
+    fn environment(&mut self) -> Result<Environment, RuntimeError> {
+        self.nested(Self::read_environment)
+    }
+
+    fn read_environment(&mut self) -> Result<Environment, RuntimeError> {
+        match self.u8()? {
+            OBJECT_ROOT => Ok(self.isolate.root.clone()),
+            OBJECT_GLOBAL => Ok(self.isolate.global()),
+            OBJECT_REFERENCE => match self.object()? {
+                Object::Environment(environment) => Ok(environment),
+                _ => Err(invalid("expecting an environment")),
+            },
+            OBJECT_NEW => {
+                let environment = self.isolate.allocate(Namespace::new())?;
+                self.objects.push(Object::Environment(environment.clone()));
+
+                let parent = if self.bool()? {
+                    Some(self.environment()?)
+                } else {
+                    None
+                };
+                let variables = self.bindings()?;
+
+                let mut namespace = environment.borrow_mut();
+                namespace.parent = parent;
+                namespace.variables = variables;
+                drop(namespace);
+
+                Ok(environment)
+            }
+            _ => Err(invalid("malformed environment")),
+        }
+    }
+
+    /// Finds the native called `name` among those installed in the isolate.
+    fn native(&self, name: &str) -> Result<Value, RuntimeError> {
+        let mut namespace = Some(self.isolate.root.clone());
+
+        while let Some(environment) = namespace {
+            if let Some(value @ Value::NativeThunk(native)) =
+                environment.borrow().variables.get(name)
+            {
+                if native.name == name {
+                    return Ok(value.clone());
+                }
+            }
+            namespace = environment.borrow().parent.clone();
+        }
+
+        Err(invalid(&format!("the native {:?} isn't installed", name)))
+    }
+
+    fn values(&mut self) -> Result<Vec<Value>, RuntimeError> {
+        (0..self.u32()?).map(|_| self.value()).collect()
+    }
+
+    fn value(&mut self) -> Result<Value, RuntimeError> {
+        self.nested(Self::read_value)
+    }
+
+    fn read_value(&mut self) -> Result<Value, RuntimeError> {
+        let value = match self.u8()? {
+            VALUE_INTEGER => Value::Integer(self.i32()?),
+            VALUE_STRING => Value::String(self.string()?),
+            VALUE_BOOLEAN => Value::Boolean(self.bool()?),
+            VALUE_SYMBOL => Value::Symbol(self.string()?),
+            VALUE_LIST => {
+                let items = self.values()?;
+                let tail = self.value()?;
+                Value::list_with_tail(items, tail)
+            }
+            VALUE_NIL => Value::Nil,
+            VALUE_NONE => Value::None,
+            VALUE_EOF => Value::Eof,
+            tag @ (VALUE_THUNK | VALUE_MACRO) => {
+                let thunk = Thunk {
+                    source: self.lambda()?,
+                    closure: self.environment()?,
+                };
+
+                if tag == VALUE_THUNK {
+                    Value::Thunk(thunk)
+                } else {
+                    Value::Macro(thunk)
+                }
+            }
+            VALUE_NATIVE_THUNK => {
+                let name = self.string()?;
+                self.native(&name)?
+            }
+            VALUE_ENVIRONMENT => Value::Environment(self.environment()?),
+            VALUE_ERROR => Value::Error(Rc::new(ErrorObject {
+                kind: self.string()?,
+                message: self.string()?,
+                irritants: self.values()?,
+            })),
+            VALUE_CONTINUATION => Value::Continuation(Rc::new(Continuation {
+                active: Cell::new(false),
+            })),
+            VALUE_VECTOR => match self.u8()? {
+                OBJECT_REFERENCE => match self.object()? {
+                    Object::Vector(items) => Value::Vector(items),
+                    _ => return Err(invalid("expecting a vector")),
+                },
+                OBJECT_NEW => {
+                    let vector = self.isolate.allocate_vector(vec![])?;
+                    if let Value::Vector(items) = &vector {
+                        self.objects.push(Object::Vector(items.clone()));
+                        let values = self.values()?;
+                        *items.borrow_mut() = values;
+                    }
+                    vector
+                }
+                _ => return Err(invalid("malformed vector")),
+            },
+            VALUE_HASH_TABLE => match self.u8()? {
+                OBJECT_REFERENCE => match self.object()? {
+                    Object::HashTable(table) => Value::HashTable(table),
+                    _ => return Err(invalid("expecting a hash table")),
+                },
+                OBJECT_NEW => {
+                    let table = self.isolate.allocate_hash_table()?;
+                    if let Value::HashTable(entries) = &table {
+                        self.objects.push(Object::HashTable(entries.clone()));
+                        for _ in 0..self.u32()? {
+                            let key = self.value()?;
+                            let value = self.value()?;
+                            entries.borrow_mut().insert(key, value);
+                        }
+                    }
+                    table
+                }
+                _ => return Err(invalid("malformed hash table")),
+            },
+            _ => return Err(invalid("malformed value")),
+        };
+
+        Ok(value)
+    }
+
+    fn lambda(&mut self) -> Result<Rc<LambdaExpr>, RuntimeError> {
+        self.nested(Self::read_lambda)
+    }
+
+    fn read_lambda(&mut self) -> Result<Rc<LambdaExpr>, RuntimeError> {
+        match self.u8()? {
+            OBJECT_REFERENCE => {
+                let index = self.u32()?;
+                match self.lambdas.get(index) {
+                    Some(Some(lambda)) => Ok(lambda.clone()),
+                    _ => Err(invalid("unknown lambda expression")),
+                }
+            }
+            OBJECT_NEW => {
+                let index = self.lambdas.len();
+                self.lambdas.push(None);
+
+                let location = self.location()?;
+                let name = self.optional_string()?;
+                let parameters = (0..self.u32()?)
+                    .map(|_| self.identifier())
+                    .collect::<Result<_, _>>()?;
+                let rest = if self.bool()? {
+                    Some(self.identifier()?)
+                } else {
+                    None
+                };
+                let body = self.exprs()?;
+
+                let lambda = Rc::new_cyclic(|this| LambdaExpr {
+                    location,
+                    this: this.clone(),
+                    name,
+                    parameters,
+                    rest,
+                    body,
+                });
+
+                self.lambdas[index] = Some(lambda.clone());
+                Ok(lambda)
+            }
+            _ => Err(invalid("malformed lambda expression")),
+        }
+    }
+
+    fn identifier(&mut self) -> Result<Rc<IdentifierExpr>, RuntimeError> {
+        Ok(Rc::new(IdentifierExpr {
+            location: self.location()?,
+            identifer: self.string()?,
+        }))
+    }
+
+    fn optional_expr(&mut self) -> Result<Option<Rc<dyn Expr>>, RuntimeError> {
+        if self.bool()? {
+            Ok(Some(self.expr()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn exprs(&mut self) -> Result<Vec<Rc<dyn Expr>>, RuntimeError> {
+        (0..self.u32()?).map(|_| self.expr()).collect()
+    }
+
+    fn expr(&mut self) -> Result<Rc<dyn Expr>, RuntimeError> {
+        self.nested(Self::read_expr)
+    }
+
+    fn read_expr(&mut self) -> Result<Rc<dyn Expr>, RuntimeError> {
+        let expr: Rc<dyn Expr> = match self.u8()? {
+            EXPR_DEFINE => Rc::new(DefineExpr {
+                location: self.location()?,
+                identifier: self.identifier()?,
+                value: self.expr()?,
+            }),
+            EXPR_CALL => Rc::new(CallExpr {
+                location: self.location()?,
+                function: self.expr()?,
+                parameters: self.exprs()?,
+            }),
+            EXPR_IDENTIFIER => self.identifier()?,
+            EXPR_INTEGER => Rc::new(IntegerLiteral {
+                location: self.location()?,
+                value: self.i32()?,
+            }),
+            EXPR_STRING => Rc::new(StringLiteral {
+                location: self.location()?,
+                value: self.string()?,
+            }),
+            EXPR_QUOTE => Rc::new(QuoteExpr {
+                location: self.location()?,
+                value: self.value()?,
+            }),
+            EXPR_LAMBDA => self.lambda()?,
+            EXPR_IF => Rc::new(IfExpr {
+                location: self.location()?,
+                condition: self.expr()?,
+                consequent: self.expr()?,
+                alternative: self.optional_expr()?,
+            }),
+            EXPR_DEFMACRO => Rc::new(DefmacroExpr {
+                location: self.location()?,
+                identifier: self.identifier()?,
+                transformer: self.lambda()?,
+            }),
+            EXPR_MACRO_CALL => Rc::new(MacroCallExpr {
+                location: self.location()?,
+                identifier: self.identifier()?,
+                operands: self.data()?,
+                expansion: RefCell::new(None),
+            }),
+            EXPR_GUARD => {
+                let location = self.location()?;
+                let variable = self.identifier()?;
+                let clauses = (0..self.u32()?)
+                    .map(|_| {
+                        Ok(GuardClause {
+                            test: self.optional_expr()?,
+                            body: self.exprs()?,
+                        })
+                    })
+                    .collect::<Result<_, RuntimeError>>()?;
+
+                Rc::new(GuardExpr {
+                    location,
+                    variable,
+                    clauses,
+                    body: self.exprs()?,
+                })
+            }
+            EXPR_EXPORT => Rc::new(ExportExpr {
+                location: self.location()?,
+                identifiers: (0..self.u32()?)
+                    .map(|_| self.identifier())
+                    .collect::<Result<_, _>>()?,
+            }),
+            EXPR_CASE => {
+                let location = self.location()?;
+                let key = self.expr()?;
+                let clauses = (0..self.u32()?)
+                    .map(|_| {
+                        let data = if self.bool()? {
+                            Some(self.values()?)
+                        } else {
+                            None
+                        };
+
+                        Ok(CaseClause {
+                            data,
+                            body: self.exprs()?,
+                        })
+                    })
+                    .collect::<Result<_, RuntimeError>>()?;
+
+                Rc::new(CaseExpr {
+                    location,
+                    key,
+                    clauses,
+                })
+            }
+            _ => return Err(invalid("malformed expression")),
+        };
+
+        Ok(expr)
+    }
+
+    fn datum(&mut self) -> Result<Datum, RuntimeError> {
+        self.nested(Self::read_datum)
+    }
+
+    fn read_datum(&mut self) -> Result<Datum, RuntimeError> {
+        let location = self.location()?;
+
+        let tag = match self.u8()? {
+            DATUM_SYMBOL => DatumTag::Symbol(self.string()?),
+            DATUM_INTEGER => DatumTag::IntegerLiteral(self.i32()?),
+            DATUM_STRING => DatumTag::StringLiteral(self.string()?),
+            DATUM_BOOLEAN => DatumTag::BooleanLiteral(self.bool()?),
+            DATUM_LIST => DatumTag::List(self.data()?),
+            DATUM_DOTTED_LIST => DatumTag::DottedList(self.data()?, Box::new(self.datum()?)),
+            DATUM_VECTOR => DatumTag::Vector(self.data()?),
+            _ => return Err(invalid("malformed datum")),
+        };
+
+        Ok(Datum { tag, location })
+    }
+
+    fn data(&mut self) -> Result<Vec<Datum>, RuntimeError> {
+        (0..self.u32()?).map(|_| self.datum()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluate::Evaluatable;
+    use crate::isolate::IsolateBuilder;
+    use crate::library;
+    use crate::port::OutputPort;
+
+    /// Evaluates `code` in `isolate`, and returns what it printed.
+    fn run(isolate: &mut Isolate, code: &str) -> String {
+        isolate.output = OutputPort::string();
+        let program = library::parse(isolate, "test", code).unwrap();
+        program.evaluate(isolate).unwrap();
+        let output = isolate.output.borrow();
+        String::from(output.contents().unwrap())
+    }
+
+    /// A snapshot binding `x` to `depth` vectors, each holding the next.
+    fn nested_vectors(depth: usize) -> Vec<u8> {
+        let mut bytes = Vec::from(MAGIC);
+        for count in [VERSION, 0, 0, 0, 1, 1] {
+            bytes.extend(count.to_le_bytes());
+        }
+        bytes.push(b'x');
+
+        for _ in 0..depth {
+            bytes.extend([VALUE_VECTOR, OBJECT_NEW]);
+            bytes.extend(1u32.to_le_bytes());
+        }
+        bytes.push(VALUE_INTEGER);
+        bytes.extend(0i32.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut warm = IsolateBuilder::new().all_modules().build();
+        run(&mut warm, include_str!("../tests/snapshot"));
+        let bytes = save(&warm).unwrap();
+
+        let mut cold = IsolateBuilder::new()
+            .all_modules()
+            .without_prelude()
+            .build();
+        restore(&mut cold, &bytes).unwrap();
+
+        assert_eq!(run(&mut cold, "(check)"), run(&mut warm, "(check)"));
+    }
+
+    #[test]
+    fn invalid_snapshots_are_errors() {
+        let mut warm = IsolateBuilder::new().without_prelude().build();
+        run(
+            &mut warm,
+            "(define (pair x) (list x x)) (define v (vector 'a \"b\"))",
+        );
+        let bytes = save(&warm).unwrap();
+
+        for end in 0..bytes.len() {
+            let mut cold = IsolateBuilder::new().without_prelude().build();
+            assert!(restore(&mut cold, &bytes[..end]).is_err());
+        }
+
+        let mut cold = IsolateBuilder::new().without_prelude().build();
+        assert!(restore(&mut cold, b"not a snapshot at all").is_err());
+
+        let mut garbage = bytes.clone();
+        for byte in garbage[MAGIC.len() + 4..].iter_mut() {
+            *byte = byte.wrapping_mul(31).wrapping_add(7);
+        }
+        let mut cold = IsolateBuilder::new().without_prelude().build();
+        assert!(restore(&mut cold, &garbage).is_err());
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        // The binding's value is one level, and each vector another.
+        let mut cold = IsolateBuilder::new().without_prelude().build();
+        assert!(restore(&mut cold, &nested_vectors(MAX_DEPTH - 1)).is_ok());
+
+        let mut cold = IsolateBuilder::new().without_prelude().build();
+        assert!(restore(&mut cold, &nested_vectors(MAX_DEPTH * 100)).is_err());
+    }
+
+    #[test]
+    fn snapshots_that_save_restore() {
+        let nest = "(define (nest n x)
+                      (if (null? n) x (nest (cdr n) (vector x))))
+                    (define (quoted n x)
+                      (if (null? n) x (quoted (cdr n) (list 'quote x))))";
+
+        for depth in [MAX_DEPTH / 2, MAX_DEPTH - 2, MAX_DEPTH - 1, MAX_DEPTH, 200] {
+            let mut warm = IsolateBuilder::new().build();
+            run(&mut warm, nest);
+            run(
+                &mut warm,
+                &format!(
+                    "(define levels (vector->list (make-vector {} 0)))
+                     (define x (nest levels 0))
+                     (define f (eval (list 'lambda '() (quoted levels 0))))",
+                    depth
+                ),
+            );
+
+            match save(&warm) {
+                Ok(bytes) => {
+                    let mut cold = IsolateBuilder::new().without_prelude().build();
+                    assert!(restore(&mut cold, &bytes).is_ok(), "depth {}", depth);
+                }
+                Err(error) => {
+                    // The binding, the closure and the quote expression take levels too.
+                    assert!(depth > MAX_DEPTH / 2, "depth {}", depth);
+                    assert!(matches!(error, RuntimeError::Unserializable { .. }));
+                }
+            }
+        }
+    }
+}
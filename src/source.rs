@@ -0,0 +1,44 @@
+use std::rc::Rc;
+
+/// Identifies code registered in `Sources`. The default id is for code that comes from no file,
+/// e.g. data turned into code by `eval`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct SourceId(usize);
+
+impl SourceId {
+    /// Counts sources from 1, in the order they were added. The default id is 0.
+    pub fn number(self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Debug)]
+pub struct Source {
+    /// A path, or a description like `-e #1` for code that isn't in a file.
+    pub name: String,
+    pub code: Rc<str>,
+}
+
+/// All the code an isolate has parsed, so that errors can be reported against it.
+#[derive(Debug, Default)]
+pub struct Sources {
+    sources: Vec<Source>,
+}
+
+impl Sources {
+    pub fn add(&mut self, name: &str, code: &str) -> SourceId {
+        self.sources.push(Source {
+            name: String::from(name),
+            code: Rc::from(code),
+        });
+        SourceId(self.sources.len())
+    }
+
+    pub fn get(&self, id: SourceId) -> Option<&Source> {
+        self.sources.get(id.0.checked_sub(1)?)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Source> {
+        self.sources.iter()
+    }
+}
@@ -1,29 +1,417 @@
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
+use std::fmt;
 use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
 
-use crate::{ast::Expr, isolate::RuntimeError};
+use crate::ast::LambdaExpr;
+use crate::isolate::{Environment, Isolate, RuntimeError};
+use crate::port::{InputPort, OutputPort};
 use std::rc::Rc;
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub enum Value {
     Integer(i32),
     String(String),
+    Boolean(bool),
+    Symbol(String),
+    Pair(Rc<Pair>),
+    Nil,
     None,
     Thunk(Thunk),
     NativeThunk(NativeThunk),
+    Macro(Thunk),
+    Eof,
+    Environment(Environment),
+    Error(Rc<ErrorObject>),
+    Continuation(Rc<Continuation>),
+    Vector(Rc<RefCell<Vec<Value>>>),
+    /// Keys are compared like `equal?` does. A key must not be modified while it is in a table,
+    /// or it can no longer be found.
+    HashTable(Rc<RefCell<HashMap<Value, Value>>>),
+    InputPort(Rc<RefCell<InputPort>>),
+    OutputPort(Rc<RefCell<OutputPort>>),
 }
 
-#[derive(Debug, Clone)]
+impl Value {
+    pub fn cons(car: Value, cdr: Value) -> Value {
+        Value::Pair(Rc::new(Pair { car, cdr }))
+    }
+
+    /// Builds a proper list out of `values`, ending with `tail`.
+    pub fn list_with_tail(values: Vec<Value>, tail: Value) -> Value {
+        values
+            .into_iter()
+            .rev()
+            .fold(tail, |cdr, car| Value::cons(car, cdr))
+    }
+
+    pub fn list(values: Vec<Value>) -> Value {
+        Value::list_with_tail(values, Value::Nil)
+    }
+
+    /// Collects the elements of a proper list, or returns `None` for anything else.
+    pub fn to_vec(&self) -> Option<Vec<Value>> {
+        let mut values = vec![];
+        let mut cur = self;
+
+        loop {
+            match cur {
+                Value::Nil => return Some(values),
+                Value::Pair(pair) => {
+                    values.push(pair.car.clone());
+                    cur = &pair.cdr;
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    /// How the value is called in stack traces.
+    pub fn procedure_name(&self) -> String {
+        match self {
+            Value::Thunk(thunk) | Value::Macro(thunk) => thunk
+                .source
+                .name
+                .clone()
+                .unwrap_or_else(|| String::from("lambda")),
+            Value::NativeThunk(native_thunk) => String::from(native_thunk.name),
+            Value::Continuation(_) => String::from("continuation"),
+            _ => format!("{:?}", self),
+        }
+    }
+
+    pub fn written(&self) -> Written<'_> {
+        Written(self)
+    }
+
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Boolean(false))
+    }
+
+    /// Whether the values are the same, like `eqv?`: pairs, vectors, procedures and other
+    /// objects are compared by identity. Strings are values rather than objects in rlisp, so
+    /// they are compared by their contents, like integers and symbols.
+    pub fn is_eqv(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (Value::Symbol(a), Value::Symbol(b)) => a == b,
+            (Value::Nil, Value::Nil) | (Value::None, Value::None) | (Value::Eof, Value::Eof) => {
+                true
+            }
+            (Value::Pair(a), Value::Pair(b)) => Rc::ptr_eq(a, b),
+            (Value::Thunk(a), Value::Thunk(b)) | (Value::Macro(a), Value::Macro(b)) => {
+                Rc::ptr_eq(&a.source, &b.source) && Rc::ptr_eq(&a.closure, &b.closure)
+            }
+            // Natives are bound once, under a name of their own.
+            (Value::NativeThunk(a), Value::NativeThunk(b)) => a.name == b.name,
+            (Value::Environment(a), Value::Environment(b)) => Rc::ptr_eq(a, b),
+            (Value::Error(a), Value::Error(b)) => Rc::ptr_eq(a, b),
+            (Value::Continuation(a), Value::Continuation(b)) => Rc::ptr_eq(a, b),
+            (Value::Vector(a), Value::Vector(b)) => Rc::ptr_eq(a, b),
+            (Value::HashTable(a), Value::HashTable(b)) => Rc::ptr_eq(a, b),
+            (Value::InputPort(a), Value::InputPort(b)) => Rc::ptr_eq(a, b),
+            (Value::OutputPort(a), Value::OutputPort(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+/// Structural equality, like `equal?`: pairs and vectors are equal when their elements are.
+/// Other values are equal when they are `eqv?`.
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        is_equal(self, other, &mut vec![])
+    }
+}
+
+/// `comparing` holds the pairs of vectors being compared. Vectors that contain themselves would
+/// otherwise be compared forever; meeting a pair again, they are taken to be equal, unless some
+/// other element differs.
+fn is_equal(left: &Value, right: &Value, comparing: &mut Vec<(*const (), *const ())>) -> bool {
+    let (mut left, mut right) = (left, right);
+
+    loop {
+        return match (left, right) {
+            (Value::Pair(a), Value::Pair(b)) => {
+                if Rc::ptr_eq(a, b) {
+                    return true;
+                }
+                if !is_equal(&a.car, &b.car, comparing) {
+                    return false;
+                }
+                left = &a.cdr;
+                right = &b.cdr;
+                continue;
+            }
+            (Value::Vector(a), Value::Vector(b)) => {
+                let key = (Rc::as_ptr(a) as *const (), Rc::as_ptr(b) as *const ());
+
+                if Rc::ptr_eq(a, b) || comparing.contains(&key) {
+                    return true;
+                }
+
+                let (a, b) = (a.borrow(), b.borrow());
+                if a.len() != b.len() {
+                    return false;
+                }
+
+                comparing.push(key);
+                let equal = a
+                    .iter()
+                    .zip(b.iter())
+                    .all(|(a, b)| is_equal(a, b, comparing));
+                comparing.pop();
+                equal
+            }
+            _ => left.is_eqv(right),
+        };
+    }
+}
+
+impl Eq for Value {}
+
+/// Vectors nested deeper than this only hash their length, so that a vector containing itself
+/// can be hashed.
+const MAX_HASH_DEPTH: usize = 4;
+
+/// Consistent with `PartialEq`: values that are `equal?` hash alike.
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_value(self, 0, state);
+    }
+}
+
+/// `depth` counts the vectors `value` is in.
+fn hash_value<H: Hasher>(value: &Value, depth: usize, state: &mut H) {
+    std::mem::discriminant(value).hash(state);
+
+    match value {
+        Value::Integer(value) => value.hash(state),
+        Value::String(value) | Value::Symbol(value) => value.hash(state),
+        Value::Boolean(value) => value.hash(state),
+        Value::Pair(_) => {
+            let mut cur = value;
+
+            while let Value::Pair(pair) = cur {
+                hash_value(&pair.car, depth, state);
+                cur = &pair.cdr;
+            }
+
+            hash_value(cur, depth, state);
+        }
+        Value::Nil | Value::None | Value::Eof => {}
+        Value::Thunk(thunk) | Value::Macro(thunk) => {
+            Rc::as_ptr(&thunk.source).hash(state);
+            Rc::as_ptr(&thunk.closure).hash(state);
+        }
+        Value::NativeThunk(native_thunk) => native_thunk.name.hash(state),
+        Value::Environment(environment) => Rc::as_ptr(environment).hash(state),
+        Value::Error(error) => Rc::as_ptr(error).hash(state),
+        Value::Continuation(continuation) => Rc::as_ptr(continuation).hash(state),
+        Value::Vector(items) => {
+            let items = items.borrow();
+            items.len().hash(state);
+
+            if depth < MAX_HASH_DEPTH {
+                for item in items.iter() {
+                    hash_value(item, depth + 1, state);
+                }
+            }
+        }
+        Value::HashTable(table) => Rc::as_ptr(table).hash(state),
+        Value::InputPort(port) => Rc::as_ptr(port).hash(state),
+        Value::OutputPort(port) => Rc::as_ptr(port).hash(state),
+    }
+}
+
+/// Like a derived `Debug`, except that vectors, hash tables and environments are written like
+/// `write` does: they may contain themselves, which `write` notices.
+impl Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Integer(value) => f.debug_tuple("Integer").field(value).finish(),
+            Value::String(value) => f.debug_tuple("String").field(value).finish(),
+            Value::Boolean(value) => f.debug_tuple("Boolean").field(value).finish(),
+            Value::Symbol(name) => f.debug_tuple("Symbol").field(name).finish(),
+            Value::Pair(pair) => f.debug_tuple("Pair").field(pair).finish(),
+            Value::Nil => write!(f, "Nil"),
+            Value::None => write!(f, "None"),
+            Value::Thunk(thunk) => f.debug_tuple("Thunk").field(thunk).finish(),
+            Value::NativeThunk(native_thunk) => {
+                f.debug_tuple("NativeThunk").field(native_thunk).finish()
+            }
+            Value::Macro(thunk) => f.debug_tuple("Macro").field(thunk).finish(),
+            Value::Eof => write!(f, "Eof"),
+            Value::Environment(_) => write!(f, "Environment({})", self.written()),
+            Value::Error(error) => f.debug_tuple("Error").field(error).finish(),
+            Value::Continuation(continuation) => {
+                f.debug_tuple("Continuation").field(continuation).finish()
+            }
+            Value::Vector(_) => write!(f, "Vector({})", self.written()),
+            Value::HashTable(_) => write!(f, "HashTable({})", self.written()),
+            Value::InputPort(port) => f.debug_tuple("InputPort").field(port).finish(),
+            Value::OutputPort(port) => f.debug_tuple("OutputPort").field(port).finish(),
+        }
+    }
+}
+
+/// Renders values in Lisp syntax, like `display`: strings are printed as they are. Use
+/// `Value::written` to print them like `write`, so that they can be read back.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        print(self, f, false, &mut vec![])
+    }
+}
+
+/// A value printed like `write` does.
+pub struct Written<'a>(&'a Value);
+
+impl fmt::Display for Written<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        print(self.0, f, true, &mut vec![])
+    }
+}
+
+/// `printing` holds the vectors being printed, since a vector may contain itself.
+fn print(
+    value: &Value,
+    f: &mut fmt::Formatter,
+    write: bool,
+    printing: &mut Vec<*const RefCell<Vec<Value>>>,
+) -> fmt::Result {
+    match value {
+        Value::Integer(value) => write!(f, "{}", value),
+        Value::String(value) if write => {
+            write!(f, "\"")?;
+            for c in value.chars() {
+                match c {
+                    '"' => write!(f, "\\\"")?,
+                    '\\' => write!(f, "\\\\")?,
+                    '\n' => write!(f, "\\n")?,
+                    '\r' => write!(f, "\\r")?,
+                    '\t' => write!(f, "\\t")?,
+                    c => write!(f, "{}", c)?,
+                }
+            }
+            write!(f, "\"")
+        }
+        Value::String(value) => write!(f, "{}", value),
+        Value::Boolean(value) => write!(f, "{}", if *value { "#t" } else { "#f" }),
+        Value::Symbol(name) => write!(f, "{}", name),
+        Value::Pair(_) => {
+            write!(f, "(")?;
+            let mut cur = value;
+
+            loop {
+                match cur {
+                    Value::Pair(pair) => {
+                        if !std::ptr::eq(cur, value) {
+                            write!(f, " ")?;
+                        }
+                        print(&pair.car, f, write, printing)?;
+                        cur = &pair.cdr;
+                    }
+                    Value::Nil => break,
+                    tail => {
+                        write!(f, " . ")?;
+                        print(tail, f, write, printing)?;
+                        break;
+                    }
+                }
+            }
+
+            write!(f, ")")
+        }
+        Value::Vector(items) if printing.contains(&Rc::as_ptr(items)) => write!(f, "#<cycle>"),
+        Value::Vector(items) => {
+            printing.push(Rc::as_ptr(items));
+            write!(f, "#(")?;
+            for (i, item) in items.borrow().iter().enumerate() {
+                if i > 0 {
+                    write!(f, " ")?;
+                }
+                print(item, f, write, printing)?;
+            }
+            printing.pop();
+            write!(f, ")")
+        }
+        Value::Nil => write!(f, "()"),
+        Value::None => write!(f, "#<unspecified>"),
+        Value::Thunk(thunk) => match &thunk.source.name {
+            Some(name) => write!(f, "#<procedure {}>", name),
+            None => write!(f, "#<procedure>"),
+        },
+        Value::NativeThunk(native_thunk) => write!(f, "#<procedure {}>", native_thunk.name),
+        Value::Macro(_) => write!(f, "#<macro {}>", value.procedure_name()),
+        Value::Eof => write!(f, "#<eof>"),
+        Value::Environment(_) => write!(f, "#<environment>"),
+        Value::Error(error) => {
+            write!(
+                f,
+                "#<error {}",
+                Value::String(error.message.clone()).written()
+            )?;
+            for irritant in error.irritants.iter() {
+                write!(f, " {}", irritant.written())?;
+            }
+            write!(f, ">")
+        }
+        Value::Continuation(_) => write!(f, "#<continuation>"),
+        Value::HashTable(table) => write!(f, "#<hash-table {}>", table.borrow().len()),
+        Value::InputPort(_) => write!(f, "#<input-port>"),
+        Value::OutputPort(_) => write!(f, "#<output-port>"),
+    }
+}
+
+#[derive(Debug)]
+pub struct Pair {
+    pub car: Value,
+    pub cdr: Value,
+}
+
+/// What `error` raises, and what internal runtime errors look like when caught.
+#[derive(Debug)]
+pub struct ErrorObject {
+    /// `error` for objects made by `error`, otherwise the kind of runtime error, e.g. `unbound`.
+    pub kind: String,
+    pub message: String,
+    pub irritants: Vec<Value>,
+}
+
+/// An escape continuation captured by `call/cc`. Invoking it unwinds the stack back to the
+/// `call/cc` call, so it can only be used while that call is in progress.
+#[derive(Debug)]
+pub struct Continuation {
+    pub active: Cell<bool>,
+}
+
+#[derive(Clone)]
 pub struct Thunk {
-    source: Rc<dyn Expr>,
-    closure: HashMap<String, Value>,
+    pub source: Rc<LambdaExpr>,
+    pub closure: Environment,
 }
 
-pub struct NativeThunkInput {
+impl Debug for Thunk {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // The closure may (indirectly) contain this thunk, so it is never printed.
+        f.debug_struct("Thunk")
+            .field("name", &self.source.name)
+            .finish()
+    }
+}
+
+pub struct NativeThunkInput<'a> {
     pub parameters: Vec<Value>,
+    pub isolate: &'a mut Isolate,
 }
 
+pub type NativeFunction = fn(input: NativeThunkInput) -> Result<Value, RuntimeError>;
+
 #[derive(Debug, Clone)]
 pub struct NativeThunk {
-    pub function: fn(input: NativeThunkInput) -> Result<Value, RuntimeError>,
+    pub name: &'static str,
+    pub function: NativeFunction,
 }
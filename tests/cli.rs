@@ -0,0 +1,38 @@
+use std::process::{Command, Output, Stdio};
+
+fn rlisp(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rlisp"))
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn bad_invocations_are_usage_errors() {
+    let invocations: [&[&str]; 5] = [
+        &["-e"],
+        &["--snapshot"],
+        &["--save-snapshot"],
+        &["--fuel=abc", "-e", "1"],
+        &["--max-heap=-1", "-e", "1"],
+    ];
+
+    for args in invocations {
+        let output = rlisp(args);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        assert!(stderr.starts_with("error: "), "{:?}: {}", args, stderr);
+        assert!(!stderr.contains("panicked"), "{:?}: {}", args, stderr);
+    }
+}
+
+#[test]
+fn numbers_are_reported() {
+    let output = rlisp(&["--fuel=abc", "-e", "1"]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(
+        stderr,
+        "error: invalid value \"abc\" for --fuel: expected a non-negative integer.\n"
+    );
+}
@@ -0,0 +1,63 @@
+use std::process::{Command, Output};
+
+/// Runs the interpreter on the `dump` sample script with `flag`.
+fn dump(flag: &str) -> Output {
+    run(flag, concat!(env!("CARGO_MANIFEST_DIR"), "/tests/dump"))
+}
+
+fn run(flag: &str, script: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rlisp"))
+        .args([flag, script])
+        .output()
+        .unwrap()
+}
+
+fn stdout(flag: &str) -> String {
+    let output = dump(flag);
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn tokens() {
+    let output = stdout("--dump=tokens");
+    assert!(output.starts_with("1:1     LParen\n1:2     Identifier(\"define\")\n"));
+    assert!(output.contains("1:21    Quasiquote\n1:22    LParen\n1:23    Unquote\n"));
+    assert!(output.contains("EOF\n"));
+}
+
+#[test]
+fn tokens_go_on_after_unknown_characters() {
+    let script = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/unknown-characters");
+    let output = run("--dump=tokens", script);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout.contains("2:8     error: unexpected character '['\n2:9 "));
+    assert!(stdout.contains("2:10    error: unexpected character ']'\n2:11    RParen\n"));
+    assert!(stdout.contains("\n3:0     EOF\n"));
+}
+
+#[test]
+fn ast() {
+    let output = stdout("--dump=ast");
+    assert!(output.starts_with("1:1  (define swap (lambda (pair) (append (list (cdr pair)) "));
+    assert!(output.contains("2:1  (define sum (let ((a 1) (b 2)) (list a b)))\n"));
+    assert_eq!(output, stdout("--show-ast"));
+}
+
+#[test]
+fn expanded() {
+    let output = stdout("--dump=expanded");
+    assert!(output.contains("2:1  (define sum ((lambda (a b) (list a b)) 1 2))\n"));
+}
+
+#[test]
+fn unknown_stages_are_errors() {
+    for flag in ["--dump=bytecode", "--dump=nothing"] {
+        let output = dump(flag);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert_eq!(output.status.code(), Some(2));
+        assert!(stderr.starts_with("error: "));
+        assert!(!stderr.contains("panicked"));
+    }
+}
@@ -0,0 +1,2 @@
+(define greeting '(hello from a loaded file))
+(define (fail-loudly) (car 'not-a-pair))
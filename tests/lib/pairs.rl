@@ -0,0 +1,5 @@
+(export swap first-of)
+
+(define (swap pair) (cons (cdr pair) (car pair)))
+(define (first-of xs) (car xs))
+(define hidden 'not-exported)